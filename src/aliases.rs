@@ -0,0 +1,317 @@
+use super::{Config, Error, Settings};
+use crate::bus::Finder;
+use crate::errors::{self, ErrorKind, InvalidArgument};
+use failure::format_err;
+use std::ffi::OsString;
+use structopt::clap;
+use structopt::StructOpt;
+
+/// A command defined in the config that runs other commands, like:
+///
+/// ```toml
+/// [aliases.focus-mode]
+/// steps = ["--player chromium pause", "--player spotify play"]
+/// on-failure = "continue"
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub(crate) struct Alias {
+    #[serde(default)]
+    on_failure: OnFailure,
+    steps: Vec<String>,
+}
+
+/// What to do when a step fails.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum OnFailure {
+    /// Skip the remaining steps.
+    #[default]
+    Stop,
+    /// Report the failure and run the remaining steps.
+    Continue,
+}
+
+impl Alias {
+    /// Check that the alias can be invoked and that all steps can be split into arguments.
+    pub(crate) fn validate(&self, name: &str) -> Result<(), Error> {
+        if !is_free_name(name) {
+            return Err(format_err!(
+                "Alias \"{}\" can never run, as the name is taken by a built-in command",
+                name
+            ));
+        }
+        if self.steps.is_empty() {
+            return Err(format_err!("Alias \"{}\" has no steps", name));
+        }
+        for step in &self.steps {
+            split_arguments(step)
+                .map_err(|error| format_err!("Alias \"{}\" has an invalid step: {}", name, error))?;
+        }
+        Ok(())
+    }
+}
+
+/// When the arguments failed to parse because the subcommand is unknown, it might be an alias.
+/// Returns the name of the alias and the global options given around it.
+pub(crate) fn invocation(error: &clap::Error, args: &[OsString]) -> Option<(String, Settings)> {
+    if !is_unknown_subcommand(error) {
+        return None;
+    }
+    let name = error.info.as_ref()?.first()?.to_string();
+
+    // Parse the options with a command that takes no arguments in place of the alias. The name
+    // may also appear as the value of an option, so only the occurrence that parses is the alias.
+    (1..args.len())
+        .filter(|&position| *args[position] == *name)
+        .find_map(|position| {
+            let mut args = args.to_vec();
+            args[position] = OsString::from("list");
+            Settings::from_iter_safe(args).ok()
+        })
+        .map(|settings| (name, settings))
+}
+
+/// Whether the name does not run a built-in command, which includes unambiguous prefixes of one.
+fn is_free_name(name: &str) -> bool {
+    match Settings::from_iter_safe(vec!["mprisctl", name]) {
+        Err(ref error) => is_unknown_subcommand(error),
+        Ok(_) => false,
+    }
+}
+
+/// Clap reports unknown subcommands that are similar to a known one as invalid.
+fn is_unknown_subcommand(error: &clap::Error) -> bool {
+    matches!(
+        error.kind,
+        clap::ErrorKind::UnrecognizedSubcommand | clap::ErrorKind::InvalidSubcommand
+    )
+}
+
+/// The built-in command that a mistyped name is probably meant to be, like "Did you mean 'play'?".
+fn suggestion(name: &str) -> Option<String> {
+    let error = Settings::clap()
+        .setting(clap::AppSettings::ColorNever)
+        .get_matches_from_safe(vec!["mprisctl", name])
+        .err()?;
+    error
+        .message
+        .lines()
+        .map(str::trim)
+        .find(|line| line.starts_with("Did you mean"))
+        .map(String::from)
+}
+
+/// Run the steps of the alias, with the finder of a batch if there is one.
+pub(crate) fn run(
+    name: &str,
+    config: &Config,
+    outer: &Settings,
+    finder: Option<&Finder>,
+) -> Result<(), Error> {
+    let alias = config.alias(name).ok_or_else(|| {
+        InvalidArgument(format!(
+            "The subcommand '{}' wasn't recognized, and there is no alias with that name in the config{}",
+            name,
+            suggestion(name).map(|suggestion| format!(". {}", suggestion)).unwrap_or_default()
+        ))
+    })?;
+
+    let mut failures = 0;
+    for step in &alias.steps {
+        if outer.verbose {
+            eprintln!("Running {}: {}", name, step);
+        }
+
+        if let Err(error) = run_step(step, config, outer, finder) {
+            match alias.on_failure {
+                OnFailure::Stop => return Err(error),
+                OnFailure::Continue => {
+                    errors::report(&error, ErrorKind::of(&error), outer.error_format);
+                    failures += 1;
+                }
+            }
+        }
+    }
+
+    if failures > 0 {
+        Err(format_err!(
+            "{} of {} steps of \"{}\" failed",
+            failures,
+            alias.steps.len(),
+            name
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+fn run_step(
+    step: &str,
+    config: &Config,
+    outer: &Settings,
+    finder: Option<&Finder>,
+) -> Result<(), Error> {
+    let mut settings = parse_command(step)?;
+
+    settings.inherit(outer);
+    config.apply(&mut settings)?;
+    match finder {
+        Some(finder) => super::run_sharing(config, &settings, finder),
+        None => super::run(config, &settings),
+    }
+}
+
+/// Parse a command line written without the program name, like `-p spotify play`.
+pub(crate) fn parse_command(line: &str) -> Result<Settings, Error> {
+    let args = command_arguments(line)?;
+    Settings::from_iter_safe(args).map_err(|error| invalid_command(line, &error))
+}
+
+/// Parse a command line like `parse_command`, which may also invoke an alias. Returns the name of
+/// the alias along with the options given around it.
+pub(crate) fn parse_invocation(line: &str) -> Result<(Settings, Option<String>), Error> {
+    let args = command_arguments(line)?;
+    match Settings::from_iter_safe(&args) {
+        Ok(settings) => Ok((settings, None)),
+        Err(error) => match invocation(&error, &args) {
+            Some((name, settings)) => Ok((settings, Some(name))),
+            None => Err(invalid_command(line, &error)),
+        },
+    }
+}
+
+fn command_arguments(line: &str) -> Result<Vec<OsString>, Error> {
+    Ok(std::iter::once(String::from("mprisctl"))
+        .chain(split_arguments(line).map_err(InvalidArgument)?)
+        .map(OsString::from)
+        .collect())
+}
+
+fn invalid_command(line: &str, error: &clap::Error) -> Error {
+    let message = error.message.lines().next().unwrap_or_default();
+    InvalidArgument(format!(
+        "Invalid command \"{}\": {}",
+        line,
+        message.trim_start_matches("error: ")
+    ))
+    .into()
+}
+
+/// Split a step into arguments like a shell would, so that quoted arguments can contain spaces.
+fn split_arguments(step: &str) -> Result<Vec<String>, String> {
+    let mut arguments = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quote = None;
+    let mut chars = step.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') | (None, '\\') => match chars.next() {
+                Some(escaped) => current.get_or_insert_with(String::new).push(escaped),
+                None => return Err(format!("\"{}\" ends with a backslash", step)),
+            },
+            (Some(_), c) => current.get_or_insert_with(String::new).push(c),
+            (None, '\'') | (None, '"') => {
+                quote = Some(c);
+                current.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => arguments.extend(current.take()),
+            (None, c) => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if quote.is_some() {
+        return Err(format!("\"{}\" has an unterminated quote", step));
+    }
+    arguments.extend(current);
+    Ok(arguments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_splits_arguments() {
+        assert_eq!(split_arguments("-p spotify  play").unwrap(), vec!["-p", "spotify", "play"]);
+        assert_eq!(
+            split_arguments(r#"format "{{artist}} - {{title}}" -p 'VLC media player'"#).unwrap(),
+            vec!["format", "{{artist}} - {{title}}", "-p", "VLC media player"]
+        );
+        assert_eq!(split_arguments(r#"a\ b "" c"#).unwrap(), vec!["a b", "", "c"]);
+        assert!(split_arguments("format \"{{title}}").is_err());
+    }
+
+    #[test]
+    fn it_recognizes_alias_invocations() {
+        let args: Vec<OsString> = vec!["x", "-p", "vlc", "focus-mode", "-v"]
+            .into_iter()
+            .map(OsString::from)
+            .collect();
+        let error = Settings::from_iter_safe(args.clone()).unwrap_err();
+        let (name, settings) = invocation(&error, &args).unwrap();
+
+        assert_eq!(name, "focus-mode");
+        assert_eq!(settings.player, Some("vlc".parse().unwrap()));
+        assert!(settings.verbose);
+    }
+
+    #[test]
+    fn it_skips_option_values_named_like_the_alias() {
+        let args: Vec<OsString> = vec!["x", "-p", "focus-mode", "focus-mode"]
+            .into_iter()
+            .map(OsString::from)
+            .collect();
+        let error = Settings::from_iter_safe(args.clone()).unwrap_err();
+        let (name, settings) = invocation(&error, &args).unwrap();
+
+        assert_eq!(name, "focus-mode");
+        assert_eq!(settings.player, Some("focus-mode".parse().unwrap()));
+    }
+
+    #[test]
+    fn it_recognizes_aliases_similar_to_commands() {
+        let args: Vec<OsString> = vec!["x", "both"].into_iter().map(OsString::from).collect();
+        let error = Settings::from_iter_safe(args.clone()).unwrap_err();
+
+        assert_eq!(invocation(&error, &args).map(|(name, _)| name).as_deref(), Some("both"));
+        assert_eq!(suggestion("both").as_deref(), Some("Did you mean 'batch'?"));
+        assert_eq!(suggestion("focus-mode"), None);
+    }
+
+    #[test]
+    fn it_parses_alias_invocations_in_command_lines() {
+        let (settings, alias) = parse_invocation("-p vlc focus-mode").unwrap();
+        assert_eq!(alias.as_deref(), Some("focus-mode"));
+        assert_eq!(settings.player, Some("vlc".parse().unwrap()));
+
+        let (_, alias) = parse_invocation("-p vlc play").unwrap();
+        assert_eq!(alias, None);
+        assert!(parse_invocation("play --bogus").is_err());
+    }
+
+    #[test]
+    fn it_ignores_other_argument_errors() {
+        let args: Vec<OsString> = vec!["x", "play", "--bogus"].into_iter().map(OsString::from).collect();
+        let error = Settings::from_iter_safe(args.clone()).unwrap_err();
+
+        assert!(invocation(&error, &args).is_none());
+    }
+
+    #[test]
+    fn it_parses_aliases() {
+        let alias: Alias = toml::from_str(r#"steps = ["-p chromium pause", "-p spotify play"]"#).unwrap();
+
+        assert_eq!(alias.on_failure, OnFailure::Stop);
+        assert!(alias.validate("focus-mode").is_ok());
+        assert!(alias.validate("play").is_err());
+        assert!(alias.validate("stat").is_err());
+        assert!(alias.validate("help").is_err());
+        assert!(alias.validate("both").is_ok());
+        assert!(toml::from_str::<Alias>(r#"steps = []
+on-failure = "retry""#)
+        .is_err());
+    }
+}
@@ -0,0 +1,149 @@
+extern crate dbus;
+
+use super::{Error, PlayerNotFound, Settings};
+use self::dbus::{Connection, Message, MessageType};
+use crate::bus::Finder;
+use crate::instance::MPRIS_PREFIX;
+use failure::Fail;
+use mpris::{DBusError, Player};
+use std::convert::TryFrom;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+const NAME_OWNER_CHANGED: &str = "type='signal',sender='org.freedesktop.DBus',\
+                                  interface='org.freedesktop.DBus',member='NameOwnerChanged',\
+                                  arg0namespace='org.mpris.MediaPlayer2'";
+
+/// Players are looked for again at least this often, in case a signal was missed.
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The player did not appear before the timeout.
+#[derive(Debug)]
+pub(crate) struct NotStarted {
+    timeout: Duration,
+    reason: String,
+}
+
+impl fmt::Display for NotStarted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Timed out after {:?} waiting for the player. {}", self.timeout, self.reason)
+    }
+}
+
+impl Fail for NotStarted {}
+
+/// Find the selected player, waiting for it to appear on the bus if it is not running yet. Waits
+/// forever without a timeout.
+pub(crate) fn find_player<'p>(
+    settings: &Settings,
+    finder: &Finder,
+    timeout: Option<Duration>,
+) -> Result<Player<'p>, Error> {
+    // Listen before the first attempt so that a player appearing in between is not missed.
+    let connection = finder.connection()?;
+    connection
+        .add_match(NAME_OWNER_CHANGED)
+        .map_err(DBusError::from)?;
+
+    let result = wait_for_player(settings, finder, connection, timeout);
+    // The connection is shared with later commands, which should not get these signals.
+    let _ = connection.remove_match(NAME_OWNER_CHANGED);
+    result
+}
+
+fn wait_for_player<'p>(
+    settings: &Settings,
+    finder: &Finder,
+    connection: &Connection,
+    timeout: Option<Duration>,
+) -> Result<Player<'p>, Error> {
+    let started = Instant::now();
+    let mut announced = false;
+    loop {
+        let error = match settings.find_player_using(finder) {
+            Ok(player) => return Ok(player),
+            Err(error) => error,
+        };
+
+        if !PlayerNotFound::may_appear(&error) {
+            return Err(error);
+        }
+
+        let remaining = match timeout {
+            Some(timeout) => match timeout.checked_sub(started.elapsed()) {
+                Some(remaining) if remaining > Duration::from_millis(0) => remaining,
+                _ => {
+                    return Err(NotStarted {
+                        timeout,
+                        reason: error.to_string(),
+                    }
+                    .into())
+                }
+            },
+            None => RETRY_INTERVAL,
+        };
+
+        if settings.verbose && !announced {
+            eprintln!("Waiting for player to appear...");
+            announced = true;
+        }
+        wait_for_new_player(connection, remaining.min(RETRY_INTERVAL));
+    }
+}
+
+fn wait_for_new_player(connection: &Connection, timeout: Duration) {
+    let deadline = Instant::now() + timeout;
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+        let timeout_ms = u32::try_from(remaining.as_millis()).unwrap_or(u32::MAX);
+        match connection.incoming(timeout_ms).next() {
+            Some(ref message) if is_new_player(message) => return,
+            Some(_) => continue,
+            None => return,
+        }
+    }
+}
+
+fn is_new_player(message: &Message) -> bool {
+    if message.msg_type() != MessageType::Signal
+        || message.member().as_deref() != Some("NameOwnerChanged")
+    {
+        return false;
+    }
+
+    match message.read3::<&str, &str, &str>() {
+        Ok((name, _old_owner, new_owner)) => is_mpris_appearance(name, new_owner),
+        Err(_) => false,
+    }
+}
+
+/// An MPRIS name that gets a new owner belongs to a player that just started.
+fn is_mpris_appearance(name: &str, new_owner: &str) -> bool {
+    name.starts_with(MPRIS_PREFIX) && !new_owner.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_recognizes_appearing_players() {
+        assert!(is_mpris_appearance("org.mpris.MediaPlayer2.spotify", ":1.42"));
+        assert!(!is_mpris_appearance("org.mpris.MediaPlayer2.spotify", ""));
+        assert!(!is_mpris_appearance("org.freedesktop.Notifications", ":1.42"));
+    }
+
+    #[test]
+    fn it_times_out_with_the_timeout_exit_code() {
+        let error: Error = NotStarted {
+            timeout: Duration::from_millis(1500),
+            reason: PlayerNotFound::NoPlayers.to_string(),
+        }
+        .into();
+
+        assert_eq!(crate::errors::ErrorKind::of(&error).exit_code(), 3);
+        assert_eq!(
+            error.to_string(),
+            "Timed out after 1.5s waiting for the player. Could not find any player"
+        );
+    }
+}
@@ -1,17 +1,60 @@
 extern crate mpris;
+use crate::bus::Finder;
+use failure::Fail;
 use mpris::Player;
+use std::fmt;
 
 use super::{Error, Settings};
 
-pub(crate) fn run<'p, F>(name: &'static str, action: F, settings: &Settings) -> Result<(), Error>
+/// The player does not accept a command. Only a failure in strict mode.
+#[derive(Debug)]
+pub(crate) struct Unsupported {
+    name: &'static str,
+    capability: &'static str,
+    identity: String,
+}
+
+impl fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} command not sent to {} as player does not accept it ({} is false).",
+            self.name, self.identity, self.capability
+        )
+    }
+}
+
+impl Fail for Unsupported {}
+
+impl Unsupported {
+    pub(crate) fn new(name: &'static str, capability: &'static str, identity: &str) -> Self {
+        Unsupported {
+            name,
+            capability,
+            identity: identity.to_string(),
+        }
+    }
+}
+
+/// Send a command to the player. `capability` is the MPRIS property that tells whether the player
+/// accepts the command, like "CanGoNext".
+pub(crate) fn run<'p, F>(
+    name: &'static str,
+    capability: &'static str,
+    action: F,
+    settings: &Settings,
+    finder: &Finder,
+) -> Result<(), Error>
 where
     F: FnOnce(&Player<'p>) -> Result<bool, mpris::DBusError>,
 {
-    let player = settings.find_player()?;
+    let player = settings.find_player(finder)?;
 
     if action(&player)? {
         command_sent(name, settings.verbose, &player);
         Ok(())
+    } else if settings.strict {
+        Err(Unsupported::new(name, capability, player.identity()).into())
     } else {
         command_not_supported(name, settings.verbose, &player);
         Ok(())
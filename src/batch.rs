@@ -0,0 +1,190 @@
+use super::{aliases, Config, Error, Settings};
+use crate::bus::Finder;
+use crate::errors::{ErrorKind, ErrorReport};
+use failure::format_err;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug)]
+pub struct Options {
+    #[structopt(name = "FILE", parse(from_os_str))]
+    /// Read commands like "-p spotify play" from this file, one per line. Reads stdin if not
+    /// given or "-". Empty lines and lines starting with "#" are skipped. Aliases from the config
+    /// can be used too.
+    ///
+    /// The result of each command is printed to stderr, like "3: ok", so that stdout only has the
+    /// output of the commands themselves.
+    file: Option<PathBuf>,
+
+    #[structopt(long = "json")]
+    /// Print each result to stderr as a JSON object.
+    json: bool,
+}
+
+/// The result of one command, printed to stderr after any output of the command itself.
+#[derive(Debug, Serialize)]
+struct Outcome<'a> {
+    line: usize,
+    command: &'a str,
+    ok: bool,
+    #[serde(flatten)]
+    error: Option<ErrorReport>,
+}
+
+impl<'a> Outcome<'a> {
+    fn new(line: usize, command: &'a str, result: &Result<(), Error>) -> Outcome<'a> {
+        Outcome {
+            line,
+            command,
+            ok: result.is_ok(),
+            error: result
+                .as_ref()
+                .err()
+                .map(|error| ErrorReport::new(error, ErrorKind::of(error))),
+        }
+    }
+}
+
+impl<'a> fmt::Display for Outcome<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.error {
+            None => write!(f, "{}: ok", self.line),
+            Some(ref error) => write!(f, "{}: {}: {}", self.line, error.kind, error.message),
+        }
+    }
+}
+
+/// Run every command with the same connection, so that players are not looked up from scratch.
+pub(crate) fn run(options: &Options, config: &Config, settings: &Settings) -> Result<(), Error> {
+    let input: Box<dyn BufRead> = match options.file {
+        Some(ref path) if path != Path::new("-") => {
+            let file = File::open(path)
+                .map_err(|error| format_err!("Cannot read commands from {}: {}", path.display(), error))?;
+            Box::new(BufReader::new(file))
+        }
+        _ => Box::new(BufReader::new(io::stdin())),
+    };
+
+    let finder = settings.player_finder()?;
+
+    let (mut count, mut failures) = (0, 0);
+    let mut unreadable = None;
+    for (index, line) in input.lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(error) => {
+                unreadable = Some((index + 1, error));
+                break;
+            }
+        };
+        let command = match command_in(&line) {
+            Some(command) => command,
+            None => continue,
+        };
+        if settings.verbose {
+            eprintln!("Running line {}: {}", index + 1, command);
+        }
+
+        let result = run_command(command, config, settings, &finder);
+        let outcome = Outcome::new(index + 1, command, &result);
+        if options.json {
+            eprintln!("{}", serde_json::to_string(&outcome)?);
+        } else {
+            eprintln!("{}", outcome);
+        }
+
+        count += 1;
+        if result.is_err() {
+            failures += 1;
+        }
+    }
+
+    match unreadable {
+        Some((line, error)) => Err(format_err!(
+            "Cannot read line {}: {} ({} of {} commands before it failed)",
+            line,
+            error,
+            failures,
+            count
+        )),
+        None if failures > 0 => Err(format_err!("{} of {} commands failed", failures, count)),
+        None => Ok(()),
+    }
+}
+
+/// The command on a line, unless it is empty or a comment.
+fn command_in(line: &str) -> Option<&str> {
+    let command = line.trim();
+    if command.is_empty() || command.starts_with('#') {
+        None
+    } else {
+        Some(command)
+    }
+}
+
+fn run_command(command: &str, config: &Config, outer: &Settings, finder: &Finder) -> Result<(), Error> {
+    let (mut settings, alias) = aliases::parse_invocation(command)?;
+    settings.inherit(outer);
+
+    match alias {
+        Some(ref name) => aliases::run(name, config, &settings, Some(finder)),
+        None => {
+            config.apply(&mut settings)?;
+            super::run_sharing(config, &settings, finder)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::InvalidArgument;
+    use crate::Command;
+
+    #[test]
+    fn it_parses_options() {
+        let settings = Settings::from_iter(vec!["x", "batch", "commands.txt", "--json"]);
+
+        match settings.command {
+            Command::Batch(options) => {
+                assert_eq!(options.file, Some(PathBuf::from("commands.txt")));
+                assert!(options.json);
+            }
+            command => panic!("Unexpected command {:?}", command),
+        }
+    }
+
+    #[test]
+    fn it_skips_empty_lines_and_comments() {
+        assert_eq!(command_in("  -p spotify play "), Some("-p spotify play"));
+        assert_eq!(command_in("   "), None);
+        assert_eq!(command_in("# pause everything"), None);
+    }
+
+    #[test]
+    fn it_prints_outcomes() {
+        let ok = Outcome::new(1, "play", &Ok(()));
+        let failed = Outcome::new(3, "-p vlc pause", &Err(InvalidArgument(String::from("Oops")).into()));
+
+        assert_eq!(ok.to_string(), "1: ok");
+        assert_eq!(failed.to_string(), "3: invalid-argument: Oops");
+        assert_eq!(
+            serde_json::to_value(&ok).unwrap(),
+            json!({"line": 1, "command": "play", "ok": true})
+        );
+        assert_eq!(
+            serde_json::to_value(&failed).unwrap(),
+            json!({
+                "line": 3,
+                "command": "-p vlc pause",
+                "ok": false,
+                "kind": "invalid-argument",
+                "message": "Oops",
+                "causes": [],
+            })
+        );
+    }
+}
@@ -0,0 +1,68 @@
+extern crate dbus;
+
+use self::dbus::{BusType, Connection};
+use failure::Error;
+use mpris::{DBusError, FindingError, Player, PlayerFinder};
+use std::cell::OnceCell;
+
+/// The D-Bus bus that players are looked for on.
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) enum Bus {
+    Session,
+    System,
+    Address(String),
+}
+
+impl Bus {
+    /// A new connection, so that signals and calls of one part do not interfere with another.
+    pub(crate) fn connect(&self) -> Result<Connection, Error> {
+        let connection = match self {
+            Bus::Session => Connection::get_private(BusType::Session),
+            Bus::System => Connection::get_private(BusType::System),
+            Bus::Address(address) => Connection::open_private(address).and_then(|connection| {
+                connection.register()?;
+                Ok(connection)
+            }),
+        };
+        Ok(connection.map_err(DBusError::from)?)
+    }
+}
+
+/// Finds players on a bus, and talks to the bus itself for everything else, like waiting for
+/// players to appear. The connections are opened once, so that a batch of commands shares them.
+pub(crate) struct Finder {
+    bus: Bus,
+    players: PlayerFinder,
+    connection: OnceCell<Connection>,
+}
+
+impl Finder {
+    pub(crate) fn new(bus: Bus) -> Result<Finder, Error> {
+        Ok(Finder {
+            players: PlayerFinder::for_connection(bus.connect()?),
+            connection: OnceCell::new(),
+            bus,
+        })
+    }
+
+    pub(crate) fn bus(&self) -> &Bus {
+        &self.bus
+    }
+
+    pub(crate) fn find_all<'p>(&self) -> Result<Vec<Player<'p>>, FindingError> {
+        self.players.find_all()
+    }
+
+    pub(crate) fn find_active<'p>(&self) -> Result<Player<'p>, FindingError> {
+        self.players.find_active()
+    }
+
+    /// The connection for calls to the bus itself, opened when first needed.
+    pub(crate) fn connection(&self) -> Result<&Connection, Error> {
+        if let Some(connection) = self.connection.get() {
+            return Ok(connection);
+        }
+        let connection = self.bus.connect()?;
+        Ok(self.connection.get_or_init(|| connection))
+    }
+}
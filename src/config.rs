@@ -0,0 +1,279 @@
+extern crate toml;
+
+use super::{Command, Error, Settings};
+use crate::aliases::Alias;
+use crate::errors::InvalidArgument;
+use crate::instance::PlayerSelector;
+use crate::suggestion::closest_match;
+use failure::format_err;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug)]
+#[structopt(rename_all = "kebab-case")]
+pub enum Options {
+    /// Print the configuration in effect, including options given on the command line
+    Show,
+}
+
+/// Defaults read from `$XDG_CONFIG_HOME/mprisctl/config.toml`. Options given on the command line
+/// take precedence.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields, default)]
+pub(crate) struct Config {
+    /// The player to control when none is given with --player.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    player: Option<String>,
+    /// Players to prefer, in order, when no player is selected.
+    priority: Vec<String>,
+    /// Players to leave out unless they are selected with --player.
+    ignore: Vec<String>,
+    /// Fail when the player does not accept a command or a template uses a missing field, unless
+    /// --no-strict is given.
+    strict: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    watch_interval: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata_format: Option<String>,
+    /// Other names for players, like `music = "spotify"`.
+    player_aliases: BTreeMap<String, String>,
+    /// Templates that the format command can use by name, like `format @short`.
+    templates: BTreeMap<String, String>,
+    /// Commands that run other commands, like `mprisctl focus-mode`.
+    aliases: BTreeMap<String, Alias>,
+}
+
+impl Config {
+    /// Read the given file, or the default file if it exists.
+    pub(crate) fn load(path: Option<&Path>) -> Result<Config, Error> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Config::default()),
+            },
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => Config::parse(&contents)
+                .map_err(|error| format_err!("Cannot read config from {}: {}", path.display(), error)),
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound && !required => {
+                Ok(Config::default())
+            }
+            Err(error) => Err(format_err!("Cannot read config from {}: {}", path.display(), error)),
+        }
+    }
+
+    fn parse(contents: &str) -> Result<Config, Error> {
+        let config: Config = toml::from_str(contents)?;
+
+        if let Some(ref player) = config.player {
+            player.parse::<PlayerSelector>().map_err(failure::err_msg)?;
+        }
+        for target in config.player_aliases.values() {
+            target.parse::<PlayerSelector>().map_err(failure::err_msg)?;
+        }
+        for (name, alias) in &config.aliases {
+            alias.validate(name)?;
+        }
+        Ok(config)
+    }
+
+    /// Fill in everything that was not given on the command line.
+    pub(crate) fn apply(&self, settings: &mut Settings) -> Result<(), Error> {
+        if settings.player.is_none() {
+            settings.player = self.player.as_ref().and_then(|player| player.parse().ok());
+        }
+        if let Some(ref mut selector) = settings.player {
+            if let Some(target) = self.alias_target(&selector.name) {
+                selector.name = target.name;
+                selector.instance = selector.instance.or(target.instance);
+            }
+        }
+
+        if settings.priority.is_empty() {
+            settings.priority = self.priority.clone();
+        }
+        if settings.ignore.is_empty() {
+            settings.ignore = self.ignore.clone();
+        }
+        if !settings.no_strict {
+            settings.strict = settings.strict || self.strict;
+        }
+
+        match settings.command {
+            Command::Metadata(ref mut options) => {
+                if let Some(ref format) = self.metadata_format {
+                    options.set_default_format(format)?;
+                }
+            }
+            Command::Format(ref mut options) => {
+                if let Some(interval) = self.watch_interval {
+                    options.set_default_watch_interval(interval);
+                }
+                if let Some(name) = options.template_name() {
+                    let template = self.template(&name)?;
+                    options.set_template(template);
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn alias(&self, name: &str) -> Option<&Alias> {
+        self.aliases.get(name)
+    }
+
+    fn alias_target(&self, name: &str) -> Option<PlayerSelector> {
+        self.player_aliases
+            .iter()
+            .find(|(alias, _)| caseless::default_caseless_match_str(alias, name))
+            .and_then(|(_, target)| target.parse().ok())
+    }
+
+    fn template(&self, name: &str) -> Result<String, Error> {
+        match self.templates.get(name) {
+            Some(template) => Ok(template.to_string()),
+            None => {
+                let suggestion = closest_match(name, self.templates.keys().map(String::as_str))
+                    .map(|suggestion| format!(" Did you mean \"@{}\"?", suggestion))
+                    .unwrap_or_default();
+                Err(InvalidArgument(format!(
+                    "There is no template named \"{}\" in the config.{}",
+                    name, suggestion
+                ))
+                .into())
+            }
+        }
+    }
+
+    /// The configuration with the options from the command line applied.
+    fn effective(&self, settings: &Settings) -> Config {
+        Config {
+            player: settings.player.as_ref().map(PlayerSelector::to_string),
+            priority: settings.priority.clone(),
+            ignore: settings.ignore.clone(),
+            strict: settings.strict,
+            ..self.clone()
+        }
+    }
+}
+
+fn default_path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+
+    Some(config_home.join("mprisctl").join("config.toml"))
+}
+
+pub(crate) fn run(options: &Options, config: &Config, settings: &Settings) -> Result<(), Error> {
+    match options {
+        Options::Show => {
+            if settings.verbose {
+                match settings.config_path.clone().or_else(default_path) {
+                    Some(path) => eprintln!("Configuration from {}:", path.display()),
+                    None => eprintln!("No configuration file"),
+                }
+            }
+            print!("{}", toml::to_string(&config.effective(settings))?);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = r#"
+player = "music"
+priority = ["spotify", "mpv"]
+ignore = ["chromium"]
+strict = true
+watch-interval = 1000
+metadata-format = "json"
+
+[player-aliases]
+music = "Spotify"
+video = "mpv#2"
+
+[templates]
+short = "{{artist}} - {{title}}"
+
+[aliases.focus-mode]
+on-failure = "continue"
+steps = ["-p chromium pause", "-p spotify play"]
+"#;
+
+    fn settings_from(args: Vec<&str>) -> Settings {
+        let mut settings = Settings::from_iter(args);
+        Config::parse(EXAMPLE).unwrap().apply(&mut settings).unwrap();
+        settings
+    }
+
+    #[test]
+    fn it_parses_config() {
+        let config = Config::parse(EXAMPLE).unwrap();
+
+        assert_eq!(config.player, Some(String::from("music")));
+        assert_eq!(config.priority, vec!["spotify", "mpv"]);
+        assert_eq!(config.watch_interval, Some(1000));
+        assert_eq!(config.player_aliases["video"], "mpv#2");
+        assert_eq!(config.templates["short"], "{{artist}} - {{title}}");
+    }
+
+    #[test]
+    fn it_rejects_unknown_keys() {
+        assert!(Config::parse("players = \"vlc\"").is_err());
+        assert!(Config::parse("player = \"vlc#0\"").is_err());
+        assert!(Config::parse("[aliases.x]\nsteps = [\"format '{{title}}\"]").is_err());
+    }
+
+    #[test]
+    fn it_applies_defaults_and_aliases() {
+        let settings = settings_from(vec!["x", "play"]);
+        assert_eq!(settings.player, Some("Spotify".parse().unwrap()));
+        assert_eq!(settings.ignore, vec!["chromium"]);
+        assert!(settings.strict);
+
+        let settings = settings_from(vec!["x", "-p", "video", "--priority", "vlc", "play"]);
+        assert_eq!(settings.player, Some("mpv#2".parse().unwrap()));
+        assert_eq!(settings.priority, vec!["vlc"]);
+
+        let settings = settings_from(vec!["x", "-p", "Video@1234", "play"]);
+        assert_eq!(settings.player, Some("mpv@1234".parse().unwrap()));
+
+        let settings = settings_from(vec!["x", "--no-strict", "play"]);
+        assert!(!settings.strict);
+    }
+
+    #[test]
+    fn it_resolves_named_templates() {
+        let mut settings = Settings::from_iter(vec!["x", "format", "@shrt"]);
+        let error = Config::parse(EXAMPLE).unwrap().apply(&mut settings).unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "There is no template named \"shrt\" in the config. Did you mean \"@short\"?"
+        );
+    }
+
+    #[test]
+    fn it_shows_effective_config() {
+        let config = Config::parse(EXAMPLE).unwrap();
+        let settings = settings_from(vec!["x", "-p", "vlc", "config", "show"]);
+        let shown: Config = toml::from_str(&toml::to_string(&config.effective(&settings)).unwrap()).unwrap();
+
+        assert_eq!(shown.player, Some(String::from("vlc")));
+        assert_eq!(shown.templates, config.templates);
+        assert_eq!(shown.aliases, config.aliases);
+    }
+}
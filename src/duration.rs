@@ -0,0 +1,59 @@
+use std::time::Duration;
+
+/// Parse a duration given on the command line, like "500ms", "30s", "2m" or "1h". Numbers without
+/// a unit are seconds.
+pub(crate) fn parse_duration(s: &str) -> Result<Duration, String> {
+    let invalid = || format!("\"{}\" is not a valid duration. Use a format like \"30s\"", s);
+
+    let text = s.trim();
+    let unit_start = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(unit_start);
+
+    let number: f64 = number.parse().map_err(|_| invalid())?;
+    let seconds_per_unit = match unit.trim() {
+        "ms" => 0.001,
+        "" | "s" | "sec" => 1.0,
+        "m" | "min" => 60.0,
+        "h" => 3600.0,
+        _ => return Err(invalid()),
+    };
+
+    Duration::try_from_secs_f64(number * seconds_per_unit).map_err(|_| invalid())
+}
+
+/// A duration that parses with `FromStr`, for `Option<Option<_>>` options as structopt does not
+/// support custom parsers for them.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) struct Timeout(pub(crate) Duration);
+
+impl std::str::FromStr for Timeout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_duration(s).map(Timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_durations() {
+        assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration("1.5m"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+    }
+
+    #[test]
+    fn it_rejects_invalid_durations() {
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("soon").is_err());
+        assert!(parse_duration("10d").is_err());
+        assert!(parse_duration("-5s").is_err());
+    }
+}
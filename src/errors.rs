@@ -0,0 +1,261 @@
+use crate::await_player::NotStarted;
+use crate::basic_command::Unsupported;
+use crate::status::NotPlaying;
+use crate::wait::TimedOut;
+use crate::PlayerNotFound;
+use failure::{Error, Fail};
+use mpris::{DBusError, PlaybackStatus};
+use std::fmt;
+
+/// Shown after the help of the main command. Keep in sync with `ErrorKind::exit_code`.
+pub(crate) const EXIT_CODES_HELP: &str = "EXIT CODES:
+    0     Success
+    1     Any other failure
+    2     The template could not be parsed or rendered
+    3     Timed out while waiting
+    4     No player is running
+    5     The player is paused (status command)
+    6     The player is stopped (status command)
+    7     The player does not accept the command (with --strict)
+    8     No running player matched --player, or several did
+    9     Communication over D-Bus failed
+    10    Invalid command line arguments";
+
+/// What kind of failure an error is. Each kind has a stable exit code.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum ErrorKind {
+    Failure,
+    TemplateError,
+    Timeout,
+    NoPlayer,
+    Paused,
+    Stopped,
+    Unsupported,
+    PlayerNotMatched,
+    DBusFailure,
+    InvalidArgument,
+}
+
+impl ErrorKind {
+    pub(crate) fn of(error: &Error) -> ErrorKind {
+        if crate::format::is_template_error(error) {
+            ErrorKind::TemplateError
+        } else if error.downcast_ref::<TimedOut>().is_some()
+            || error.downcast_ref::<NotStarted>().is_some()
+        {
+            ErrorKind::Timeout
+        } else if error.downcast_ref::<Unsupported>().is_some() {
+            ErrorKind::Unsupported
+        } else if error.downcast_ref::<InvalidArgument>().is_some() {
+            ErrorKind::InvalidArgument
+        } else if let Some(not_found) = error.downcast_ref::<PlayerNotFound>() {
+            match not_found {
+                PlayerNotFound::NoPlayers => ErrorKind::NoPlayer,
+                PlayerNotFound::NotMatched { .. }
+                | PlayerNotFound::Ambiguous { .. }
+                | PlayerNotFound::NoInstance { .. } => ErrorKind::PlayerNotMatched,
+            }
+        } else if let Some(NotPlaying(status)) = error.downcast_ref() {
+            match status {
+                PlaybackStatus::Stopped => ErrorKind::Stopped,
+                _ => ErrorKind::Paused,
+            }
+        } else if error
+            .iter_chain()
+            .any(|cause| cause.downcast_ref::<DBusError>().is_some())
+        {
+            ErrorKind::DBusFailure
+        } else {
+            ErrorKind::Failure
+        }
+    }
+
+    pub(crate) fn exit_code(self) -> i32 {
+        use self::ErrorKind::*;
+
+        match self {
+            Failure => 1,
+            TemplateError => 2,
+            Timeout => 3,
+            NoPlayer => 4,
+            Paused => 5,
+            Stopped => 6,
+            Unsupported => 7,
+            PlayerNotMatched => 8,
+            DBusFailure => 9,
+            InvalidArgument => 10,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        use self::ErrorKind::*;
+
+        match self {
+            Failure => "failure",
+            TemplateError => "template-error",
+            Timeout => "timeout",
+            NoPlayer => "no-player",
+            Paused => "paused",
+            Stopped => "stopped",
+            Unsupported => "unsupported",
+            PlayerNotMatched => "player-not-matched",
+            DBusFailure => "dbus-failure",
+            InvalidArgument => "invalid-argument",
+        }
+    }
+}
+
+/// An argument was accepted by the parser, but is not valid.
+#[derive(Debug)]
+pub(crate) struct InvalidArgument(pub(crate) String);
+
+impl fmt::Display for InvalidArgument {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Fail for InvalidArgument {}
+
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub(crate) enum ErrorFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl ErrorFormat {
+    pub(crate) fn variants() -> [&'static str; 2] {
+        ["text", "json"]
+    }
+
+    /// Find the error format without parsing the arguments, for when parsing them failed.
+    pub(crate) fn of_raw_args<I: IntoIterator<Item = String>>(args: I) -> ErrorFormat {
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let value = if arg == "--error-format" {
+                args.next()
+            } else {
+                arg.strip_prefix("--error-format=").map(String::from)
+            };
+
+            if let Some(format) = value.and_then(|value| value.parse().ok()) {
+                return format;
+            }
+        }
+        ErrorFormat::default()
+    }
+}
+
+impl std::str::FromStr for ErrorFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match caseless::default_case_fold_str(s).as_str() {
+            "text" => Ok(ErrorFormat::Text),
+            "json" => Ok(ErrorFormat::Json),
+            _ => Err(format!("\"{}\" is not a valid error format", s)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ErrorReport {
+    pub(crate) kind: &'static str,
+    pub(crate) message: String,
+    causes: Vec<String>,
+}
+
+impl ErrorReport {
+    pub(crate) fn new(error: &Error, kind: ErrorKind) -> ErrorReport {
+        ErrorReport {
+            kind: kind.name(),
+            message: error.to_string(),
+            causes: error.iter_causes().map(|cause| cause.to_string()).collect(),
+        }
+    }
+}
+
+/// Print the error to stderr in the given format.
+pub(crate) fn report(error: &Error, kind: ErrorKind, format: ErrorFormat) {
+    match format {
+        ErrorFormat::Text => {
+            eprintln!("{}", error);
+            for cause in error.iter_causes() {
+                eprintln!("\nCaused by {}", cause);
+            }
+        }
+        ErrorFormat::Json => {
+            match serde_json::to_string(&ErrorReport::new(error, kind)) {
+                Ok(json) => eprintln!("{}", json),
+                Err(_) => eprintln!("{}", error),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_maps_failures_to_exit_codes() {
+        let exit_code_of = |error: Error| ErrorKind::of(&error).exit_code();
+
+        assert_eq!(exit_code_of(failure::err_msg("Oops")), 1);
+        assert_eq!(exit_code_of(PlayerNotFound::NoPlayers.into()), 4);
+        assert_eq!(
+            exit_code_of(
+                PlayerNotFound::Ambiguous {
+                    name: String::from("s"),
+                    candidates: vec![],
+                }
+                .into()
+            ),
+            8
+        );
+        assert_eq!(exit_code_of(NotPlaying(PlaybackStatus::Paused).into()), 5);
+        assert_eq!(exit_code_of(NotPlaying(PlaybackStatus::Stopped).into()), 6);
+        assert_eq!(
+            exit_code_of(Unsupported::new("Next", "CanGoNext", "Spotify").into()),
+            7
+        );
+        assert_eq!(
+            exit_code_of(InvalidArgument(String::from("Bad")).into()),
+            10
+        );
+    }
+
+    #[test]
+    fn it_documents_all_exit_codes() {
+        use self::ErrorKind::*;
+
+        let kinds = [
+            Failure,
+            TemplateError,
+            Timeout,
+            NoPlayer,
+            Paused,
+            Stopped,
+            Unsupported,
+            PlayerNotMatched,
+            DBusFailure,
+            InvalidArgument,
+        ];
+        for kind in &kinds {
+            let line = format!("\n    {:<6}", kind.exit_code());
+            assert!(EXIT_CODES_HELP.contains(&line), "{:?} is not documented", kind);
+        }
+    }
+
+    #[test]
+    fn it_finds_error_format_in_raw_arguments() {
+        let format_of = |args: &[&str]| {
+            ErrorFormat::of_raw_args(args.iter().map(|arg| arg.to_string()))
+        };
+
+        assert_eq!(format_of(&["x", "play"]), ErrorFormat::Text);
+        assert_eq!(format_of(&["x", "--error-format", "json", "bogus"]), ErrorFormat::Json);
+        assert_eq!(format_of(&["x", "bogus", "--error-format=json"]), ErrorFormat::Json);
+    }
+}
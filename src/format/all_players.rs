@@ -0,0 +1,133 @@
+use super::handlebars::Handlebars;
+use super::change_filter::ChangeFilter;
+use super::context::Globals;
+use super::render_template;
+use crate::bus::Finder;
+use crate::metadata::{MetadataView, PlayerView};
+use crate::Settings;
+use failure::Error;
+use mpris::FindingError;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Template context when rendering all players at once.
+#[derive(Debug, Serialize)]
+pub(crate) struct AllPlayersView<'a> {
+    players: Vec<PlayerView<'a>>,
+    current: Option<PlayerView<'a>>,
+}
+
+impl<'a> AllPlayersView<'a> {
+    fn new(players: Vec<PlayerView<'a>>) -> Self {
+        let current = players.iter().find(|player| player.is_active()).cloned();
+        AllPlayersView { players, current }
+    }
+}
+
+impl AllPlayersView<'static> {
+    pub(crate) fn sample() -> AllPlayersView<'static> {
+        AllPlayersView::new(vec![PlayerView::sample()])
+    }
+}
+
+/// Collect all players into the data that templates are rendered with. Players that cannot be
+/// read, like players that quit meanwhile, are left out and reported.
+pub(crate) fn snapshot(finder: &Finder, settings: &Settings) -> Result<Value, Error> {
+    let (data, problems) = read_players(finder, settings, true)?;
+    for problem in problems {
+        eprintln!("{}", problem);
+    }
+    Ok(data)
+}
+
+/// The data of all readable players, and what went wrong with the others. Unless the selected
+/// player is required, it may be missing too, leaving `current` null.
+fn read_players(
+    finder: &Finder,
+    settings: &Settings,
+    require_selected: bool,
+) -> Result<(Value, Vec<String>), Error> {
+    let players = match finder.find_all() {
+        Ok(players) => settings.without_ignored(players),
+        Err(FindingError::NoPlayerFound) => vec![],
+        Err(FindingError::DBusError(err)) => return Err(err.into()),
+    };
+    let mut problems = vec![];
+
+    // An empty list of players is fine, but a player selected by name must exist.
+    let active_name = if players.is_empty() {
+        None
+    } else {
+        match settings.find_player_using(finder) {
+            Ok(player) => Some(player.unique_name().to_string()),
+            Err(error) if !require_selected => {
+                problems.push(error.to_string());
+                None
+            }
+            Err(error) => return Err(error),
+        }
+    };
+
+    let metadata: Vec<_> = players
+        .iter()
+        .filter_map(|player| match player.get_metadata() {
+            Ok(metadata) => Some((player, metadata)),
+            Err(error) => {
+                problems.push(format!("Cannot read {}: {}", player.identity(), error));
+                None
+            }
+        })
+        .collect();
+
+    let mut views = Vec::with_capacity(metadata.len());
+    for (player, metadata) in &metadata {
+        match MetadataView::from_player(metadata, player) {
+            Ok(metadata_view) => {
+                let is_active = active_name.as_deref() == Some(player.unique_name());
+                views.push(PlayerView::new(metadata_view, is_active));
+            }
+            Err(error) => problems.push(format!("Cannot read {}: {}", player.identity(), error)),
+        }
+    }
+
+    Ok((serde_json::to_value(AllPlayersView::new(views))?, problems))
+}
+
+/// Render all players until stopped. Players that cannot be read are skipped, and each problem is
+/// reported when it first happens rather than ending the watch.
+pub(crate) fn watch(
+    handlebars: &Handlebars,
+    globals: &Globals,
+    settings: &Settings,
+    finder: &Finder,
+    filter: &mut ChangeFilter,
+    interval: u32,
+) -> Result<(), Error> {
+    let mut reported = HashSet::new();
+    loop {
+        match read_players(finder, settings, false) {
+            Ok((data, problems)) => {
+                let problems: HashSet<String> = problems.into_iter().collect();
+                for problem in problems.difference(&reported) {
+                    eprintln!("{}", problem);
+                }
+                reported = problems;
+
+                let output = render_template(handlebars, globals, &data)?;
+                if let Some(output) = filter.offer(output, &data, Instant::now())? {
+                    println!("{}", output);
+                }
+            }
+            Err(error) => {
+                let problem = format!("Cannot read players: {}", error);
+                if !reported.contains(&problem) {
+                    eprintln!("{}", problem);
+                }
+                reported = std::iter::once(problem).collect();
+            }
+        }
+        thread::sleep(Duration::from_millis(u64::from(interval)));
+    }
+}
@@ -0,0 +1,120 @@
+use super::handlebars::*;
+
+pub(crate) fn upper(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    if let Some(value) = h.param(0).map(|p| p.value()) {
+        out.write(&value.render().to_uppercase())?;
+    }
+    Ok(())
+}
+
+pub(crate) fn lower(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    if let Some(value) = h.param(0).map(|p| p.value()) {
+        out.write(&value.render().to_lowercase())?;
+    }
+    Ok(())
+}
+
+pub(crate) fn title_case(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    if let Some(value) = h.param(0).map(|p| p.value()) {
+        out.write(&to_title_case(&value.render()))?;
+    }
+    Ok(())
+}
+
+fn to_title_case(string: &str) -> String {
+    let mut result = String::with_capacity(string.len());
+    let mut at_word_start = true;
+
+    for c in string.chars() {
+        if c.is_whitespace() {
+            at_word_start = true;
+            result.push(c);
+        } else if at_word_start {
+            at_word_start = false;
+            result.extend(c.to_uppercase());
+        } else {
+            result.extend(c.to_lowercase());
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::format::test_handlebars;
+
+    #[test]
+    fn it_changes_case() {
+        let handlebars = test_handlebars();
+        let values = json!({"title": "In Amber Clad"});
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{upper title}}"#, &values)
+                .unwrap(),
+            "IN AMBER CLAD"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{lower title}}"#, &values)
+                .unwrap(),
+            "in amber clad"
+        );
+    }
+
+    #[test]
+    fn it_renders_title_case() {
+        let handlebars = test_handlebars();
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{title-case "the QUICK  brown-fox"}}"#, &())
+                .unwrap(),
+            "The Quick  Brown-fox"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{title-case "östen med resten"}}"#, &())
+                .unwrap(),
+            "Östen Med Resten"
+        );
+    }
+
+    #[test]
+    fn it_renders_nothing_on_missing_values() {
+        let handlebars = test_handlebars();
+
+        assert_eq!(handlebars.render_template(r#"{{upper}}"#, &()).unwrap(), "");
+        assert_eq!(
+            handlebars.render_template(r#"{{lower null}}"#, &()).unwrap(),
+            ""
+        );
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{title-case missing}}"#, &())
+                .unwrap(),
+            ""
+        );
+    }
+}
@@ -0,0 +1,252 @@
+use crate::errors::InvalidArgument;
+use crate::suggestion::closest_match;
+use failure::Error;
+use serde::Serialize;
+use serde_json::Value;
+use std::time::{Duration, Instant};
+
+/// A maximum output rate given on the command line, like `2/s` or `10/m`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) struct Rate {
+    min_interval: Duration,
+}
+
+impl std::str::FromStr for Rate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("\"{}\" is not a valid rate. Use a format like \"2/s\"", s);
+
+        let (count, period) = match s.find('/') {
+            Some(index) => (&s[..index], &s[index + 1..]),
+            None => (s, "s"),
+        };
+        let period = match period {
+            "s" | "sec" | "second" => 1.0,
+            "m" | "min" | "minute" => 60.0,
+            _ => return Err(invalid()),
+        };
+        let count: f64 = count.trim().parse().map_err(|_| invalid())?;
+
+        if count > 0.0 && count.is_finite() {
+            Ok(Rate {
+                min_interval: Duration::from_secs_f64(period / count),
+            })
+        } else {
+            Err(invalid())
+        }
+    }
+}
+
+/// Decides which rendered outputs to print when watching.
+///
+/// Outputs are printed when they differ from the last printed output, or when one of `fields`
+/// changed if any fields are given. Changes must stay the same for the debounce time before they
+/// are printed, and the latest change is held back while the maximum rate would be exceeded.
+#[derive(Debug)]
+pub(crate) struct ChangeFilter {
+    fields: Vec<String>,
+    debounce: Duration,
+    min_interval: Duration,
+    last_key: Option<Value>,
+    last_printed_at: Option<Instant>,
+    pending: Option<Pending>,
+}
+
+#[derive(Debug)]
+struct Pending {
+    key: Value,
+    output: String,
+    since: Instant,
+}
+
+impl ChangeFilter {
+    pub(crate) fn new(
+        fields: &[String],
+        known_fields: &[String],
+        debounce: Duration,
+        max_rate: Option<Rate>,
+    ) -> Result<ChangeFilter, Error> {
+        for field in fields {
+            if !known_fields.contains(field) {
+                let suggestion = closest_match(field, known_fields.iter().map(String::as_str))
+                    .map(|suggestion| format!(" Did you mean \"{}\"?", suggestion))
+                    .unwrap_or_default();
+                return Err(InvalidArgument(format!(
+                    "Cannot watch unknown field \"{}\".{}",
+                    field, suggestion
+                ))
+                .into());
+            }
+        }
+
+        Ok(ChangeFilter {
+            fields: fields.to_vec(),
+            debounce,
+            min_interval: max_rate.map(|rate| rate.min_interval).unwrap_or_default(),
+            last_key: None,
+            last_printed_at: None,
+            pending: None,
+        })
+    }
+
+    /// Offer a newly rendered output, made from `data`. Returns the output to print, if any.
+    ///
+    /// Should be called regularly even when nothing changes, so held back outputs are printed.
+    pub(crate) fn offer<T: Serialize>(
+        &mut self,
+        output: String,
+        data: &T,
+        now: Instant,
+    ) -> Result<Option<String>, Error> {
+        let key = if self.fields.is_empty() {
+            Value::String(output.clone())
+        } else {
+            let data = serde_json::to_value(data)?;
+            Value::Array(self.fields.iter().map(|field| data[field].clone()).collect())
+        };
+
+        if self.last_key.as_ref() == Some(&key) {
+            // Changes that were reverted before being printed, like the empty metadata some
+            // players emit when changing tracks.
+            self.pending = None;
+            return Ok(None);
+        }
+
+        match self.pending {
+            Some(ref mut pending) if pending.key == key => pending.output = output,
+            _ => self.pending = Some(Pending { key, output, since: now }),
+        }
+
+        Ok(self.take_ready(now))
+    }
+
+    fn take_ready(&mut self, now: Instant) -> Option<String> {
+        let is_settled = match self.pending {
+            Some(ref pending) => now.duration_since(pending.since) >= self.debounce,
+            None => false,
+        };
+        let is_allowed = match self.last_printed_at {
+            Some(printed_at) => now.duration_since(printed_at) >= self.min_interval,
+            None => true,
+        };
+
+        if is_settled && is_allowed {
+            let pending = self.pending.take()?;
+            self.last_key = Some(pending.key);
+            self.last_printed_at = Some(now);
+            Some(pending.output)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(fields: &[&str], debounce: u64, max_rate: Option<&str>) -> ChangeFilter {
+        let fields: Vec<String> = fields.iter().map(|field| field.to_string()).collect();
+        let known_fields = vec![String::from("title"), String::from("positionInSeconds")];
+
+        ChangeFilter::new(
+            &fields,
+            &known_fields,
+            Duration::from_millis(debounce),
+            max_rate.map(|rate| rate.parse().unwrap()),
+        )
+        .unwrap()
+    }
+
+    fn offer(filter: &mut ChangeFilter, title: &str, position: u64, at: Instant) -> Option<String> {
+        let data = json!({"title": title, "positionInSeconds": position});
+        let output = format!("{} {}", title, position);
+        filter.offer(output, &data, at).unwrap()
+    }
+
+    #[test]
+    fn it_prints_changed_outputs() {
+        let mut filter = filter(&[], 0, None);
+        let start = Instant::now();
+
+        assert_eq!(offer(&mut filter, "a", 1, start), Some(String::from("a 1")));
+        assert_eq!(offer(&mut filter, "a", 1, start), None);
+        assert_eq!(offer(&mut filter, "a", 2, start), Some(String::from("a 2")));
+    }
+
+    #[test]
+    fn it_only_prints_when_selected_fields_change() {
+        let mut filter = filter(&["title"], 0, None);
+        let start = Instant::now();
+
+        assert_eq!(offer(&mut filter, "a", 1, start), Some(String::from("a 1")));
+        assert_eq!(offer(&mut filter, "a", 2, start), None);
+        assert_eq!(offer(&mut filter, "b", 3, start), Some(String::from("b 3")));
+    }
+
+    #[test]
+    fn it_debounces_changes() {
+        let mut filter = filter(&["title"], 500, None);
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        assert_eq!(offer(&mut filter, "a", 1, at(0)), None);
+        assert_eq!(offer(&mut filter, "a", 2, at(500)), Some(String::from("a 2")));
+
+        // A burst that settles on a new title.
+        assert_eq!(offer(&mut filter, "", 0, at(1000)), None);
+        assert_eq!(offer(&mut filter, "b", 0, at(1100)), None);
+        assert_eq!(offer(&mut filter, "b", 1, at(1500)), None);
+        assert_eq!(offer(&mut filter, "b", 1, at(1600)), Some(String::from("b 1")));
+
+        // A burst that goes back to the printed title.
+        assert_eq!(offer(&mut filter, "", 0, at(2000)), None);
+        assert_eq!(offer(&mut filter, "b", 2, at(2100)), None);
+        assert_eq!(offer(&mut filter, "b", 3, at(3000)), None);
+    }
+
+    #[test]
+    fn it_limits_the_rate() {
+        let mut filter = filter(&[], 0, Some("2/s"));
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        assert_eq!(offer(&mut filter, "a", 1, at(0)), Some(String::from("a 1")));
+        assert_eq!(offer(&mut filter, "a", 2, at(100)), None);
+        assert_eq!(offer(&mut filter, "a", 3, at(200)), None);
+        assert_eq!(offer(&mut filter, "a", 3, at(500)), Some(String::from("a 3")));
+    }
+
+    #[test]
+    fn it_parses_rates() {
+        assert_eq!(
+            "2/s".parse::<Rate>().unwrap().min_interval,
+            Duration::from_millis(500)
+        );
+        assert_eq!(
+            "10/m".parse::<Rate>().unwrap().min_interval,
+            Duration::from_secs(6)
+        );
+        assert_eq!("4".parse::<Rate>().unwrap().min_interval, Duration::from_millis(250));
+        assert!("0/s".parse::<Rate>().is_err());
+        assert!("fast".parse::<Rate>().is_err());
+        assert!("2/h".parse::<Rate>().is_err());
+    }
+
+    #[test]
+    fn it_rejects_unknown_fields() {
+        let error = ChangeFilter::new(
+            &[String::from("titel")],
+            &[String::from("title")],
+            Duration::from_millis(0),
+            None,
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Cannot watch unknown field \"titel\". Did you mean \"title\"?"
+        );
+    }
+}
@@ -0,0 +1,262 @@
+use super::handlebars::template::{
+    HelperTemplate, Parameter, Template, TemplateElement, TemplateMapping,
+};
+use super::handlebars::Handlebars;
+use crate::suggestion::closest_match;
+use std::fmt;
+
+/// Helpers that render their block using a different context, where field names no longer refer to
+/// the top level fields.
+const CONTEXT_CHANGING_HELPERS: [&str; 2] = ["each", "with"];
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Problem {
+    line: usize,
+    column: usize,
+    message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+/// Walks a compiled template and reports all fields that are not in `fields` and all helpers that
+/// are not registered in `handlebars`.
+///
+/// Suggestions for misspelled helpers are taken from `helper_names`.
+pub(crate) fn check_template(
+    template: &Template,
+    handlebars: &Handlebars,
+    fields: &[String],
+    helper_names: &[&str],
+) -> Vec<Problem> {
+    let mut checker = Checker {
+        handlebars,
+        fields,
+        helper_names,
+        problems: Vec::new(),
+    };
+    checker.check_template(template, 0, (1, 1));
+    checker.problems
+}
+
+struct Checker<'a> {
+    handlebars: &'a Handlebars,
+    fields: &'a [String],
+    helper_names: &'a [&'a str],
+    problems: Vec<Problem>,
+}
+
+impl<'a> Checker<'a> {
+    fn check_template(&mut self, template: &Template, depth: usize, position: (usize, usize)) {
+        for (index, element) in template.elements.iter().enumerate() {
+            let position = template
+                .mapping
+                .as_ref()
+                .and_then(|mapping| mapping.get(index))
+                .map(|&TemplateMapping(line, column)| (line, column))
+                .unwrap_or(position);
+
+            self.check_element(element, depth, position);
+        }
+    }
+
+    fn check_element(&mut self, element: &TemplateElement, depth: usize, position: (usize, usize)) {
+        use self::TemplateElement::*;
+
+        match element {
+            Expression(ref param) | HTMLExpression(ref param) => {
+                self.check_parameter(param, depth, position, true)
+            }
+            HelperExpression(ref helper) | HelperBlock(ref helper) => {
+                self.check_helper(helper, depth, position)
+            }
+            _ => {}
+        }
+    }
+
+    fn check_helper(&mut self, helper: &HelperTemplate, depth: usize, position: (usize, usize)) {
+        if self.handlebars.get_helper(&helper.name).is_none() {
+            let message = with_suggestion(
+                format!("Unknown helper \"{}\".", helper.name),
+                closest_match(&helper.name, self.helper_names.iter().cloned()),
+            );
+            self.add_problem(position, message);
+        }
+
+        for param in helper.params.iter().chain(helper.hash.values()) {
+            self.check_parameter(param, depth, position, false);
+        }
+
+        let block_depth = if CONTEXT_CHANGING_HELPERS.contains(&helper.name.as_str()) {
+            depth + 1
+        } else {
+            depth
+        };
+
+        if let Some(ref template) = helper.template {
+            self.check_template(template, block_depth, position);
+        }
+        if let Some(ref inverse) = helper.inverse {
+            self.check_template(inverse, depth, position);
+        }
+    }
+
+    fn check_parameter(
+        &mut self,
+        param: &Parameter,
+        depth: usize,
+        position: (usize, usize),
+        is_bare: bool,
+    ) {
+        match param {
+            Parameter::Name(ref name) => self.check_name(name, depth, position, is_bare),
+            Parameter::Subexpression(ref subexpression) => {
+                self.check_element(&subexpression.element, depth, position)
+            }
+            Parameter::Literal(_) => {}
+        }
+    }
+
+    fn check_name(&mut self, name: &str, depth: usize, position: (usize, usize), is_bare: bool) {
+        // {{helper}} without any parameters is parsed as a plain name.
+        if is_bare && self.handlebars.get_helper(name).is_some() {
+            return;
+        }
+
+        let mut path = name;
+        let mut depth = depth;
+
+        if path.starts_with("@root.") {
+            path = &path["@root.".len()..];
+            depth = 0;
+        }
+        while path.starts_with("../") {
+            path = &path["../".len()..];
+            depth = depth.saturating_sub(1);
+        }
+        if path.starts_with("this.") {
+            path = &path["this.".len()..];
+        } else if path.starts_with("./") {
+            path = &path["./".len()..];
+        }
+
+        // Names inside of #each and #with refers to something else than the top level fields, and
+        // @-prefixed names are special variables.
+        if depth > 0 || path.starts_with('@') || path == "this" || path == "." {
+            return;
+        }
+
+        let field = path
+            .split(|c| ['.', '/', '['].contains(&c))
+            .next()
+            .unwrap_or_default();
+
+        if !field.is_empty() && !self.fields.iter().any(|known| known == field) {
+            let message = with_suggestion(
+                format!("Unknown field \"{}\".", field),
+                closest_match(field, self.fields.iter().map(String::as_str)),
+            );
+            self.add_problem(position, message);
+        }
+    }
+
+    fn add_problem(&mut self, (line, column): (usize, usize), message: String) {
+        self.problems.push(Problem {
+            line,
+            column,
+            message,
+        });
+    }
+}
+
+fn with_suggestion(message: String, suggestion: Option<&str>) -> String {
+    match suggestion {
+        Some(suggestion) => format!("{} Did you mean \"{}\"?", message, suggestion),
+        None => message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(source: &str) -> Vec<String> {
+        let mut handlebars = Handlebars::new();
+        handlebars.register_helper("join", Box::new(crate::format::join::helper));
+        handlebars.register_template_string("main", source).unwrap();
+
+        let fields: Vec<String> = vec!["title", "artists", "artistsString", "volume"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+
+        check_template(
+            handlebars.get_template("main").unwrap(),
+            &handlebars,
+            &fields,
+            &["if", "each", "with", "join"],
+        )
+        .into_iter()
+        .map(|problem| problem.to_string())
+        .collect()
+    }
+
+    #[test]
+    fn it_accepts_valid_templates() {
+        let no_problems: Vec<String> = vec![];
+
+        assert_eq!(check("{{title}} - {{artistsString}}"), no_problems);
+        assert_eq!(
+            check("{{#if title}}{{title}}{{else}}{{volume}}{{/if}}"),
+            no_problems
+        );
+        assert_eq!(
+            check("{{#each artists}}{{this}}{{@index}}{{../title}}{{/each}}"),
+            no_problems
+        );
+        assert_eq!(check("{{artists.[0]}} {{@root.title}}"), no_problems);
+    }
+
+    #[test]
+    fn it_reports_unknown_fields() {
+        assert_eq!(
+            check("{{title}}\n  {{artistString}}"),
+            vec![String::from(
+                "2:3: Unknown field \"artistString\". Did you mean \"artistsString\"?"
+            )]
+        );
+
+        assert_eq!(
+            check("{{#if title}}{{bogus}}{{/if}}"),
+            vec![String::from("1:14: Unknown field \"bogus\".")]
+        );
+
+        assert_eq!(
+            check("{{#each artists}}{{../titel}}{{/each}}"),
+            vec![String::from(
+                "1:18: Unknown field \"titel\". Did you mean \"title\"?"
+            )]
+        );
+    }
+
+    #[test]
+    fn it_reports_unknown_helpers() {
+        assert_eq!(
+            check("{{jion \", \" artists}}"),
+            vec![String::from(
+                "1:1: Unknown helper \"jion\". Did you mean \"join\"?"
+            )]
+        );
+
+        assert_eq!(
+            check("{{join \", \" (frobnicate titel)}}"),
+            vec![
+                String::from("1:1: Unknown helper \"frobnicate\"."),
+                String::from("1:1: Unknown field \"titel\". Did you mean \"title\"?"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,75 @@
+extern crate chrono;
+extern crate hostname;
+
+use self::chrono::{Local, SecondsFormat};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::env;
+
+/// Values that every template can use next to the fields of the player.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Globals {
+    env: BTreeMap<String, String>,
+    hostname: String,
+}
+
+impl Globals {
+    /// Only variables in `allowed_env` are exposed, so templates cannot leak secrets by accident.
+    pub(crate) fn new(allowed_env: &[String]) -> Globals {
+        let env = allowed_env
+            .iter()
+            .filter_map(|name| env::var(name).ok().map(|value| (name.clone(), value)))
+            .collect();
+
+        Globals {
+            env,
+            hostname: hostname::get_hostname().unwrap_or_default(),
+        }
+    }
+
+    /// Only `USER` in the environment, on a host called "workstation".
+    pub(crate) fn sample() -> Globals {
+        let mut env = BTreeMap::new();
+        env.insert(String::from("USER"), String::from("mbergmark"));
+
+        Globals {
+            env,
+            hostname: String::from("workstation"),
+        }
+    }
+
+    pub(crate) fn context<'a, T: Serialize>(&'a self, data: &'a T) -> TemplateContext<'a, T> {
+        TemplateContext {
+            data,
+            env: &self.env,
+            now: Now::new(),
+            hostname: &self.hostname,
+        }
+    }
+}
+
+/// The full data that templates are rendered with.
+#[derive(Debug, Serialize)]
+pub(crate) struct TemplateContext<'a, T: Serialize> {
+    #[serde(flatten)]
+    data: &'a T,
+    env: &'a BTreeMap<String, String>,
+    now: Now,
+    hostname: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+struct Now {
+    iso: String,
+    epoch: i64,
+}
+
+impl Now {
+    fn new() -> Now {
+        let now = Local::now();
+        Now {
+            iso: now.to_rfc3339_opts(SecondsFormat::Secs, false),
+            epoch: now.timestamp(),
+        }
+    }
+}
@@ -0,0 +1,126 @@
+extern crate chrono;
+
+use self::chrono::format::{Item, StrftimeItems};
+use self::chrono::{DateTime, FixedOffset, Local, TimeZone, Utc};
+use super::handlebars::*;
+use super::math::number_of_value;
+use serde_json::Value;
+use std::fmt::Write;
+
+const DEFAULT_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%:z";
+
+pub(crate) fn helper(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let format = match h.hash_get("format") {
+        Some(param) => param.value().render(),
+        None => String::from(DEFAULT_FORMAT),
+    };
+    let items = StrftimeItems::new(&format).collect::<Vec<_>>();
+    if items.contains(&Item::Error) {
+        return Err(RenderError::new(format!(
+            "date: Invalid format \"{}\"",
+            format
+        )));
+    }
+
+    let utc = h
+        .hash_get("utc")
+        .and_then(|param| param.value().as_bool())
+        .unwrap_or(false);
+
+    if let Some(date) = h.param(0).and_then(|p| date_of_value(p.value())) {
+        let mut output = String::new();
+        let written = if utc {
+            write!(output, "{}", date.with_timezone(&Utc).format_with_items(items.into_iter()))
+        } else {
+            write!(output, "{}", date.with_timezone(&Local).format_with_items(items.into_iter()))
+        };
+        written.map_err(|_| RenderError::new(format!("date: Cannot render \"{}\"", format)))?;
+        out.write(&output)?;
+    }
+    Ok(())
+}
+
+/// Timestamps are either seconds since the Unix epoch, or RFC 3339 strings like the ones in
+/// `now.iso`.
+fn date_of_value(val: &Value) -> Option<DateTime<FixedOffset>> {
+    if let Some(text) = val.as_str() {
+        if let Ok(date) = DateTime::parse_from_rfc3339(text) {
+            return Some(date);
+        }
+    }
+
+    let seconds = number_of_value(val)?;
+    let whole_seconds = seconds.floor();
+    let nanoseconds = ((seconds - whole_seconds) * 1_000_000_000.0) as u32;
+
+    Utc.timestamp_opt(whole_seconds as i64, nanoseconds)
+        .single()
+        .map(|date| date.fixed_offset())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::format::test_handlebars;
+
+    #[test]
+    fn it_formats_epoch_timestamps() {
+        let handlebars = test_handlebars();
+        let values = json!({"now": {"epoch": 1_545_742_800}});
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{date now.epoch utc=true}}"#, &values)
+                .unwrap(),
+            "2018-12-25T13:00:00+00:00"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{date now.epoch format="%d/%m %H:%M" utc=true}}"#, &values)
+                .unwrap(),
+            "25/12 13:00"
+        );
+    }
+
+    #[test]
+    fn it_formats_iso_timestamps() {
+        let handlebars = test_handlebars();
+        let values = json!({"now": {"iso": "2018-12-25T14:00:00+01:00"}});
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{date now.iso format="%H:%M" utc=true}}"#, &values)
+                .unwrap(),
+            "13:00"
+        );
+    }
+
+    #[test]
+    fn it_renders_nothing_on_bad_values() {
+        let handlebars = test_handlebars();
+
+        assert_eq!(
+            handlebars.render_template(r#"{{date missing}}"#, &()).unwrap(),
+            ""
+        );
+        assert_eq!(
+            handlebars.render_template(r#"{{date "yesterday"}}"#, &()).unwrap(),
+            ""
+        );
+    }
+
+    #[test]
+    fn it_fails_on_invalid_formats() {
+        let handlebars = test_handlebars();
+
+        assert!(handlebars
+            .render_template(r#"{{date 0 format="%Q"}}"#, &())
+            .is_err());
+    }
+}
@@ -0,0 +1,344 @@
+use super::handlebars::*;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Helper that pipes values through an external command, like `{{exec "my-filter" title}}`.
+///
+/// Commands are run using `sh -c` and get each value on a line of its own on stdin. Their output
+/// is rendered without the trailing newline. Outputs are cached per track, so slow commands are
+/// not rerun every time the template is rendered while watching. Failures are not cached, so
+/// commands that failed are tried again on the next render.
+pub(crate) struct Exec {
+    enabled: bool,
+    watching: bool,
+    timeout: Duration,
+    cache: Mutex<HashMap<CacheKey, String>>,
+    /// Commands that failed while watching, to only warn about each failure once.
+    failing: Mutex<HashSet<CacheKey>>,
+}
+
+/// The track of the current context, which is a player of the list with --all-players, the
+/// command and its input.
+type CacheKey = (Option<String>, String, String);
+
+/// Forget all outputs when there are this many, so that watching for a long time does not use
+/// more and more memory.
+const MAX_CACHED_OUTPUTS: usize = 256;
+
+impl Exec {
+    pub(crate) fn new(timeout: Duration) -> Exec {
+        Exec {
+            enabled: true,
+            watching: false,
+            timeout,
+            cache: Mutex::new(HashMap::new()),
+            failing: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// A helper that renders failed commands as empty with a warning, so that one failure does not
+    /// stop watching.
+    pub(crate) fn watching(timeout: Duration) -> Exec {
+        Exec {
+            watching: true,
+            ..Exec::new(timeout)
+        }
+    }
+
+    /// A helper that fails with a hint about `--allow-exec` instead of running anything.
+    pub(crate) fn disabled() -> Exec {
+        Exec {
+            enabled: false,
+            ..Exec::new(Duration::from_secs(0))
+        }
+    }
+}
+
+impl HelperDef for Exec {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Handlebars,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg>,
+    ) -> Result<Option<ScopedJson<'reg, 'rc>>, RenderError> {
+        if !self.enabled {
+            return Err(RenderError::new(
+                "exec: Running commands is only allowed with --allow-exec",
+            ));
+        }
+
+        let command = match h.param(0).and_then(|param| param.value().as_str()) {
+            Some(command) => command.to_string(),
+            None => return Err(RenderError::new("exec: The first parameter must be a command")),
+        };
+        let input = h
+            .params()
+            .iter()
+            .skip(1)
+            .map(|param| format!("{}\n", param.value().render()))
+            .collect::<String>();
+
+        let track_id = rc
+            .evaluate(ctx, "trackId", false)
+            .ok()
+            .and_then(Value::as_str)
+            .map(String::from);
+
+        let key = (track_id, command, input);
+        let cached = self
+            .cache
+            .lock()
+            .map_err(|_| RenderError::new("exec: Cache is unavailable"))?
+            .get(&key)
+            .cloned();
+        let output = match cached {
+            Some(output) => output,
+            None => {
+                // The cache is not locked while the command runs.
+                let result = run_command(&key.1, &key.2, self.timeout);
+                let mut failing = self
+                    .failing
+                    .lock()
+                    .map_err(|_| RenderError::new("exec: Cache is unavailable"))?;
+                let output = match result {
+                    Ok(output) => {
+                        failing.remove(&key);
+                        output
+                    }
+                    Err(error) if self.watching => {
+                        if failing.insert(key) {
+                            eprintln!("Warning: {}", error.desc);
+                        }
+                        return Ok(Some(ScopedJson::Derived(Value::String(String::new()))));
+                    }
+                    Err(error) => return Err(error),
+                };
+
+                let mut cache = self
+                    .cache
+                    .lock()
+                    .map_err(|_| RenderError::new("exec: Cache is unavailable"))?;
+                if cache.len() >= MAX_CACHED_OUTPUTS {
+                    cache.clear();
+                }
+                cache.insert(key, output.clone());
+                output
+            }
+        };
+
+        Ok(Some(ScopedJson::Derived(Value::String(output))))
+    }
+}
+
+fn run_command(command: &str, input: &str, timeout: Duration) -> Result<String, RenderError> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|error| RenderError::new(format!("exec: Cannot run \"{}\": {}", command, error)))?;
+
+    // Write and read from other threads, so commands that ignore their input or write a lot of
+    // output cannot block us past the timeout.
+    if let Some(mut stdin) = child.stdin.take() {
+        let input = input.to_string();
+        thread::spawn(move || stdin.write_all(input.as_bytes()));
+    }
+    let reader = child.stdout.take().map(|mut stdout| {
+        thread::spawn(move || {
+            let mut output = Vec::new();
+            stdout.read_to_end(&mut output).map(|_| output)
+        })
+    });
+
+    let started = Instant::now();
+    let status = loop {
+        let status = child
+            .try_wait()
+            .map_err(|error| RenderError::new(format!("exec: \"{}\": {}", command, error)))?;
+        if let Some(status) = status {
+            break status;
+        }
+        if started.elapsed() >= timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(RenderError::new(format!(
+                "exec: \"{}\" did not finish within {} ms",
+                command,
+                timeout.as_millis()
+            )));
+        }
+        thread::sleep(Duration::from_millis(5));
+    };
+
+    if !status.success() {
+        return Err(RenderError::new(format!(
+            "exec: \"{}\" failed with {}",
+            command, status
+        )));
+    }
+
+    let output = match reader.map(|reader| reader.join()) {
+        Some(Ok(Ok(output))) => output,
+        _ => return Err(RenderError::new(format!("exec: Cannot read output of \"{}\"", command))),
+    };
+
+    Ok(String::from_utf8_lossy(&output)
+        .trim_end_matches('\n')
+        .to_string())
+}
+
+// The tests run commands using `sh`.
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::format::test_handlebars;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn it_pipes_values_through_commands() {
+        let mut handlebars = test_handlebars();
+        handlebars.register_helper("exec", Box::new(Exec::new(Duration::from_secs(5))));
+        let values = json!({"title": "In Amber Clad", "albumName": "Halo 2"});
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{exec "tr a-z A-Z" title}}"#, &values)
+                .unwrap(),
+            "IN AMBER CLAD"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{exec "paste -sd /" title albumName}}"#, &values)
+                .unwrap(),
+            "In Amber Clad/Halo 2"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{upper (exec "cut -c 4-8" title)}}"#, &values)
+                .unwrap(),
+            "AMBER"
+        );
+    }
+
+    #[test]
+    fn it_caches_outputs_until_the_track_changes() {
+        let counter = env::temp_dir().join(format!("mprisctl-exec-test-{}", std::process::id()));
+        let _ = fs::remove_file(&counter);
+        let template = format!(
+            r#"{{{{exec "echo >> {0}; wc -l < {0}"}}}}"#,
+            counter.display()
+        );
+        let mut handlebars = test_handlebars();
+        handlebars.register_helper("exec", Box::new(Exec::new(Duration::from_secs(5))));
+
+        let render = |track_id: &str| {
+            handlebars
+                .render_template(&template, &json!({ "trackId": track_id }))
+                .unwrap()
+                .trim()
+                .to_string()
+        };
+
+        assert_eq!(render("first"), "1");
+        assert_eq!(render("first"), "1");
+        assert_eq!(render("second"), "2");
+
+        let _ = fs::remove_file(&counter);
+    }
+
+    #[test]
+    fn it_fails_on_slow_or_failing_commands() {
+        let mut handlebars = test_handlebars();
+        handlebars.register_helper("exec", Box::new(Exec::new(Duration::from_millis(50))));
+
+        let error = handlebars
+            .render_template(r#"{{exec "sleep 5"}}"#, &())
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("exec: \"sleep 5\" did not finish within 50 ms"));
+
+        assert!(handlebars.render_template(r#"{{exec "false"}}"#, &()).is_err());
+    }
+
+    #[test]
+    fn it_renders_failing_commands_as_empty_when_watching() {
+        let mut handlebars = test_handlebars();
+        handlebars.register_helper("exec", Box::new(Exec::watching(Duration::from_millis(50))));
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"[{{exec "false"}}] {{exec "cat" "ok"}}"#, &())
+                .unwrap(),
+            "[] ok"
+        );
+    }
+
+    #[test]
+    fn it_retries_failed_commands_when_watching() {
+        let counter = env::temp_dir().join(format!("mprisctl-exec-retry-{}", std::process::id()));
+        let _ = fs::remove_file(&counter);
+        let template = format!(
+            r#"{{{{exec "echo >> {0}; test $(wc -l < {0}) -gt 1 && echo ok"}}}}"#,
+            counter.display()
+        );
+        let mut handlebars = test_handlebars();
+        handlebars.register_helper("exec", Box::new(Exec::watching(Duration::from_secs(5))));
+
+        assert_eq!(handlebars.render_template(&template, &()).unwrap(), "");
+        assert_eq!(handlebars.render_template(&template, &()).unwrap(), "ok");
+
+        let _ = fs::remove_file(&counter);
+    }
+
+    #[test]
+    fn it_caches_outputs_per_player_track() {
+        let counter = env::temp_dir().join(format!("mprisctl-exec-players-{}", std::process::id()));
+        let _ = fs::remove_file(&counter);
+        let template = format!(
+            r#"{{{{#each players}}}}{{{{exec "echo >> {0}; wc -l < {0}"}}}} {{{{/each}}}}"#,
+            counter.display()
+        );
+        let mut handlebars = test_handlebars();
+        handlebars.register_helper("exec", Box::new(Exec::new(Duration::from_secs(5))));
+        let players = json!({"players": [{"trackId": "a"}, {"trackId": "b"}]});
+
+        let render = || {
+            handlebars
+                .render_template(&template, &players)
+                .unwrap()
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+
+        assert_eq!(render(), "1 2");
+        assert_eq!(render(), "1 2");
+
+        let _ = fs::remove_file(&counter);
+    }
+
+    #[test]
+    fn it_fails_when_not_allowed() {
+        let mut handlebars = test_handlebars();
+        handlebars.register_helper("exec", Box::new(Exec::disabled()));
+
+        let error = handlebars
+            .render_template(r#"{{exec "echo hi"}}"#, &())
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("exec: Running commands is only allowed with --allow-exec"));
+    }
+}
@@ -0,0 +1,82 @@
+use super::handlebars::*;
+use serde_json::Value;
+
+pub(crate) fn helper(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let first_artist = h
+        .params()
+        .iter()
+        .map(|param| param.value())
+        .filter_map(|value| match value {
+            Value::Array(ref array) => array.iter().find(|value| !value.is_null()).cloned(),
+            Value::String(ref string) => string
+                .split(", ")
+                .next()
+                .filter(|artist| !artist.is_empty())
+                .map(|artist| Value::String(artist.to_string())),
+            Value::Null => None,
+            _ => Some(value.clone()),
+        })
+        .next();
+
+    if let Some(artist) = first_artist {
+        out.write(artist.render().as_ref())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::format::test_handlebars;
+
+    #[test]
+    fn it_renders_first_artist() {
+        let handlebars = test_handlebars();
+        let values = json!({
+            "artists": ["Martin O'Donnell", "Michael Salvatori"],
+            "artistsString": "Martin O'Donnell, Michael Salvatori",
+        });
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{first-artist artists}}"#, &values)
+                .unwrap(),
+            "Martin O'Donnell"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{first-artist artistsString}}"#, &values)
+                .unwrap(),
+            "Martin O'Donnell"
+        );
+    }
+
+    #[test]
+    fn it_falls_back_to_later_values() {
+        let handlebars = test_handlebars();
+        let values = json!({
+            "artists": [],
+            "albumArtists": ["Various Artists"],
+        });
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{first-artist artists albumArtists}}"#, &values)
+                .unwrap(),
+            "Various Artists"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{first-artist missing}}"#, &values)
+                .unwrap(),
+            ""
+        );
+    }
+}
@@ -0,0 +1,280 @@
+use super::handlebars::*;
+use serde_json::Value;
+use std::collections::HashMap;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum IconSet {
+    Unicode,
+    NerdFont,
+    Ascii,
+}
+
+impl IconSet {
+    pub(crate) fn variants() -> [&'static str; 3] {
+        ["unicode", "nerd-font", "ascii"]
+    }
+
+    /// Glyphs of the set, in the same order as `ICON_NAMES`.
+    fn glyphs(self) -> [&'static str; 12] {
+        use self::IconSet::*;
+
+        match self {
+            Unicode => [
+                "▶", "⏸", "⏹", "", "🔂", "🔁", "🔀", "", "🔇", "🔈", "🔉", "🔊",
+            ],
+            NerdFont => [
+                "\u{f04b}",
+                "\u{f04c}",
+                "\u{f04d}",
+                "\u{f0457}",
+                "\u{f0458}",
+                "\u{f0456}",
+                "\u{f049d}",
+                "\u{f049e}",
+                "\u{f0581}",
+                "\u{f057f}",
+                "\u{f0580}",
+                "\u{f057e}",
+            ],
+            Ascii => [">", "||", "[]", "", "(1)", "(*)", "~", "", "x", ".", "o", "O"],
+        }
+    }
+}
+
+impl std::str::FromStr for IconSet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match caseless::default_case_fold_str(s).as_str() {
+            "unicode" => Ok(IconSet::Unicode),
+            "nerd-font" | "nerdfont" | "nerd" => Ok(IconSet::NerdFont),
+            "ascii" => Ok(IconSet::Ascii),
+            _ => Err(format!("\"{}\" is not a valid icon set", s)),
+        }
+    }
+}
+
+const ICON_NAMES: [&str; 12] = [
+    "playing",
+    "paused",
+    "stopped",
+    "loop-none",
+    "loop-track",
+    "loop-playlist",
+    "shuffled",
+    "unshuffled",
+    "volume-muted",
+    "volume-low",
+    "volume-medium",
+    "volume-high",
+];
+
+/// A glyph override given on the command line, like `playing=>`.
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct IconOverride {
+    name: String,
+    glyph: String,
+}
+
+impl std::str::FromStr for IconOverride {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, '=');
+        let name = parts.next().unwrap_or_default();
+        let glyph = parts
+            .next()
+            .ok_or_else(|| format!("\"{}\" is not in NAME=GLYPH format", s))?;
+
+        if ICON_NAMES.contains(&name) {
+            Ok(IconOverride {
+                name: name.to_string(),
+                glyph: glyph.to_string(),
+            })
+        } else {
+            Err(format!(
+                "\"{}\" is not a valid icon name. Valid names are: {}",
+                name,
+                ICON_NAMES.join(", ")
+            ))
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Icons {
+    glyphs: HashMap<String, String>,
+}
+
+impl Icons {
+    pub(crate) fn new(set: IconSet, overrides: &[IconOverride]) -> Icons {
+        let mut glyphs: HashMap<String, String> = ICON_NAMES
+            .iter()
+            .zip(set.glyphs().iter())
+            .map(|(name, glyph)| (name.to_string(), glyph.to_string()))
+            .collect();
+
+        for icon_override in overrides {
+            glyphs.insert(icon_override.name.clone(), icon_override.glyph.clone());
+        }
+
+        Icons { glyphs }
+    }
+}
+
+/// Determine which icon represents the given value.
+///
+/// Strings are treated as either `playbackStatus` or `loopStatus`, booleans as `isShuffled` and
+/// numbers as `volume`.
+fn icon_name(value: &Value) -> Option<&'static str> {
+    match value {
+        Value::String(ref s) => match s.as_str() {
+            "Playing" => Some("playing"),
+            "Paused" => Some("paused"),
+            "Stopped" => Some("stopped"),
+            "None" => Some("loop-none"),
+            "Track" => Some("loop-track"),
+            "Playlist" => Some("loop-playlist"),
+            _ => None,
+        },
+        Value::Bool(true) => Some("shuffled"),
+        Value::Bool(false) => Some("unshuffled"),
+        Value::Number(ref num) => num.as_f64().map(|volume| {
+            if volume <= 0.0 {
+                "volume-muted"
+            } else if volume < 0.34 {
+                "volume-low"
+            } else if volume < 0.67 {
+                "volume-medium"
+            } else {
+                "volume-high"
+            }
+        }),
+        _ => None,
+    }
+}
+
+impl HelperDef for Icons {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Handlebars,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let name = match h.param(0).and_then(|param| icon_name(param.value())) {
+            Some(name) => name,
+            None => return Ok(()),
+        };
+
+        // Allow templates to override single glyphs, like {{icon playbackStatus playing="P"}}
+        if let Some(glyph) = h.hash_get(name) {
+            out.write(glyph.value().render().as_ref())?;
+        } else if let Some(glyph) = self.glyphs.get(name) {
+            out.write(glyph)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::test_handlebars;
+
+    #[test]
+    fn it_renders_icons_for_statuses() {
+        let mut handlebars = test_handlebars();
+        handlebars.register_helper("icon", Box::new(Icons::new(IconSet::Ascii, &[])));
+        let values = json!({
+            "playbackStatus": "Paused",
+            "loopStatus": "Track",
+            "isShuffled": true,
+        });
+
+        assert_eq!(
+            handlebars
+                .render_template(
+                    r#"{{icon playbackStatus}} {{icon loopStatus}} {{icon isShuffled}}"#,
+                    &values
+                )
+                .unwrap(),
+            "|| (1) ~"
+        );
+    }
+
+    #[test]
+    fn it_renders_icons_for_volume_ranges() {
+        let mut handlebars = test_handlebars();
+        handlebars.register_helper("icon", Box::new(Icons::new(IconSet::Unicode, &[])));
+
+        let render = |volume: f64| {
+            handlebars
+                .render_template(r#"{{icon volume}}"#, &json!({ "volume": volume }))
+                .unwrap()
+        };
+
+        assert_eq!(render(0.0), "🔇");
+        assert_eq!(render(0.2), "🔈");
+        assert_eq!(render(0.6300000190734863), "🔉");
+        assert_eq!(render(1.2), "🔊");
+    }
+
+    #[test]
+    fn it_uses_overrides() {
+        let overrides = vec!["playing=PLAY".parse().unwrap()];
+        let mut handlebars = test_handlebars();
+        handlebars.register_helper("icon", Box::new(Icons::new(IconSet::NerdFont, &overrides)));
+        let values = json!({"playbackStatus": "Playing"});
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{icon playbackStatus}}"#, &values)
+                .unwrap(),
+            "PLAY"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{icon playbackStatus playing="P"}}"#, &values)
+                .unwrap(),
+            "P"
+        );
+    }
+
+    #[test]
+    fn it_renders_nothing_on_unknown_values() {
+        let mut handlebars = test_handlebars();
+        handlebars.register_helper("icon", Box::new(Icons::new(IconSet::Unicode, &[])));
+
+        assert_eq!(
+            handlebars.render_template(r#"{{icon "bad"}}"#, &()).unwrap(),
+            ""
+        );
+        assert_eq!(handlebars.render_template(r#"{{icon}}"#, &()).unwrap(), "");
+    }
+
+    #[test]
+    fn it_parses_overrides() {
+        assert_eq!(
+            "loop-none=-".parse::<IconOverride>(),
+            Ok(IconOverride {
+                name: String::from("loop-none"),
+                glyph: String::from("-"),
+            })
+        );
+
+        assert_eq!(
+            "volume-high==".parse::<IconOverride>(),
+            Ok(IconOverride {
+                name: String::from("volume-high"),
+                glyph: String::from("="),
+            })
+        );
+
+        assert!("playing".parse::<IconOverride>().is_err());
+        assert!("louder=!".parse::<IconOverride>().is_err());
+    }
+}
@@ -6,7 +6,7 @@ pub(crate) fn helper(
     _: &Handlebars,
     _: &Context,
     _: &mut RenderContext,
-    out: &mut Output,
+    out: &mut dyn Output,
 ) -> HelperResult {
     if let Some(joining) = h.param(0) {
         let joiner = joining.value().render();
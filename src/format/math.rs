@@ -0,0 +1,189 @@
+use super::handlebars::*;
+use serde_json::{Number, Value};
+
+/// Read a number from a value. Strings are parsed, as the output of other helpers in
+/// subexpressions are strings.
+pub(crate) fn number_of_value(value: &Value) -> Option<f64> {
+    let num: Option<f64> = match value {
+        Value::Number(ref num) => num.as_f64(),
+        Value::String(ref s) => s.trim().parse().ok(),
+        _ => None,
+    };
+    num.filter(|num| num.is_finite())
+}
+
+/// Convert a number into JSON, using integers when there is no fractional part so that they render
+/// as "63" rather than "63.0".
+pub(crate) fn value_of_number(num: f64) -> Value {
+    if num.fract() == 0.0 && num.abs() < 2f64.powi(53) {
+        Value::from(num as i64)
+    } else {
+        Number::from_f64(num).map(Value::Number).unwrap_or(Value::Null)
+    }
+}
+
+/// Helper that folds all of its parameters using an arithmetic operation, like `{{mul volume 100}}`
+/// or `{{add discNumber trackNumber 1}}`.
+///
+/// Renders nothing (or `null` in subexpressions) if any of the parameters are not numbers, or if the
+/// result is not a finite number.
+pub(crate) struct Arithmetic(pub(crate) fn(f64, f64) -> f64);
+
+impl HelperDef for Arithmetic {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Handlebars,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg>,
+    ) -> Result<Option<ScopedJson<'reg, 'rc>>, RenderError> {
+        let operation = self.0;
+        let mut numbers = h.params().iter().map(|param| number_of_value(param.value()));
+
+        let result = match numbers.next() {
+            Some(first) => numbers.fold(first, |acc, num| match (acc, num) {
+                (Some(acc), Some(num)) => Some(operation(acc, num)),
+                _ => None,
+            }),
+            None => None,
+        };
+
+        Ok(Some(ScopedJson::Derived(
+            result
+                .filter(|num| num.is_finite())
+                .map(value_of_number)
+                .unwrap_or(Value::Null),
+        )))
+    }
+}
+
+/// Helper that rounds a number to the nearest integer, or to the given number of decimals.
+///
+///   {{round 2.5}} => 3
+///   {{round 0.6351 2}} => 0.64
+pub(crate) struct Round;
+
+impl HelperDef for Round {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Handlebars,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg>,
+    ) -> Result<Option<ScopedJson<'reg, 'rc>>, RenderError> {
+        let num = h.param(0).and_then(|param| number_of_value(param.value()));
+        let decimals = h
+            .param(1)
+            .and_then(|param| param.value().as_u64())
+            .unwrap_or(0)
+            .min(15) as i32;
+
+        let rounded = num.map(|num| {
+            let factor = 10f64.powi(decimals);
+            (num * factor).round() / factor
+        });
+
+        Ok(Some(ScopedJson::Derived(
+            rounded.map(value_of_number).unwrap_or(Value::Null),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::test_handlebars;
+
+    #[test]
+    fn it_calculates() {
+        let handlebars = test_handlebars();
+        let values = json!({
+            "trackNumber": 3,
+            "lengthInSeconds": 200,
+            "positionInSeconds": 50,
+        });
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{add trackNumber 1}}"#, &values)
+                .unwrap(),
+            "4"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{sub lengthInSeconds positionInSeconds}}"#, &values)
+                .unwrap(),
+            "150"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{mul 2 1.5 2}}"#, &values)
+                .unwrap(),
+            "6"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{div positionInSeconds lengthInSeconds}}"#, &values)
+                .unwrap(),
+            "0.25"
+        );
+    }
+
+    #[test]
+    fn it_rounds() {
+        let handlebars = test_handlebars();
+        let values = json!({"volume": 0.6300000190734863});
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{round (mul volume 100)}}"#, &values)
+                .unwrap(),
+            "63"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{round volume 2}}"#, &values)
+                .unwrap(),
+            "0.63"
+        );
+
+        assert_eq!(
+            handlebars.render_template(r#"{{round 2.5}}"#, &()).unwrap(),
+            "3"
+        );
+    }
+
+    #[test]
+    fn it_renders_nothing_on_bad_values() {
+        let handlebars = test_handlebars();
+
+        assert_eq!(
+            handlebars.render_template(r#"{{add 1 missing}}"#, &()).unwrap(),
+            ""
+        );
+
+        assert_eq!(
+            handlebars.render_template(r#"{{div 1 0}}"#, &()).unwrap(),
+            ""
+        );
+
+        assert_eq!(
+            handlebars.render_template(r#"{{round "bad"}}"#, &()).unwrap(),
+            ""
+        );
+
+        assert_eq!(handlebars.render_template(r#"{{mul}}"#, &()).unwrap(), "");
+    }
+
+    #[test]
+    fn it_reads_numbers_from_strings() {
+        assert_eq!(number_of_value(&json!("12.5")), Some(12.5));
+        assert_eq!(number_of_value(&json!(" 3 ")), Some(3.0));
+        assert_eq!(number_of_value(&json!("NaN")), None);
+        assert_eq!(number_of_value(&json!(true)), None);
+    }
+}
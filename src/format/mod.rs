@@ -1,16 +1,50 @@
 extern crate handlebars;
+extern crate regex;
 
+mod all_players;
+mod case;
+mod change_filter;
+mod check;
+mod context;
+mod date;
+mod exec;
+mod first_artist;
+mod icon;
 mod join;
+mod math;
+mod number;
 mod or;
+mod replace;
+mod script;
 mod time;
+mod trim;
 
-use self::handlebars::{no_escape, Handlebars};
+use self::all_players::AllPlayersView;
+use self::change_filter::{ChangeFilter, Rate};
+use self::context::Globals;
+use self::exec::Exec;
+use self::handlebars::{no_escape, Handlebars, HelperDef, RenderError};
+use self::icon::{IconOverride, IconSet, Icons};
+use self::math::{Arithmetic, Round};
 use super::Settings;
-use failure::Error;
-use crate::metadata::MetadataView;
+use crate::bus::Finder;
+use crate::suggestion::closest_match;
+use failure::{format_err, Error, Fail};
+use crate::metadata::{MetadataView, PlayerInfo};
 use mpris::Player;
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 use structopt::StructOpt;
 
+/// Names of the helpers built into Handlebars, which cannot be listed from the registry.
+const BUILT_IN_HELPER_NAMES: &[&str] = &[
+    "if", "unless", "each", "with", "lookup", "raw", "log", "eq", "ne", "gt", "gte", "lt", "lte",
+    "and", "or", "not",
+];
+
 #[derive(StructOpt, Debug)]
 pub struct Options {
     #[structopt(short = "w", long = "watch")]
@@ -18,71 +52,527 @@ pub struct Options {
     /// of metadata changes in the player.
     watch: bool,
 
+    #[structopt(short = "i", long = "watch-interval", value_name = "MILLISECONDS")]
+    /// Rerender at close to this interval when watching. Shorter time means quicker updates, while
+    /// longer time means less resource utilization. Defaults to 250.
+    watch_interval: Option<u32>,
+
     #[structopt(
-        short = "i",
-        long = "watch-interval",
-        value_name = "MILLISECONDS",
-        default_value = "250"
+        long = "on-change",
+        value_name = "FIELDS",
+        requires = "watch",
+        raw(use_delimiter = "true", number_of_values = "1")
     )]
-    /// Rerender at close to this interval when watching. Shorter time means quicker updates, while
-    /// longer time means less resource utilization.
-    watch_interval: u32,
+    /// Only output when one of these comma-separated fields changes, like "title,artists", instead
+    /// of every time the rendered template changes.
+    on_change: Vec<String>,
+
+    #[structopt(long = "debounce", value_name = "MILLISECONDS", requires = "watch")]
+    /// Wait until a change has stayed the same for this long before outputting it. Useful as
+    /// players often send incomplete metadata while changing tracks.
+    debounce: Option<u64>,
+
+    #[structopt(long = "max-rate", value_name = "RATE", requires = "watch")]
+    /// Output at most this often, like "2/s" or "10/m". The latest change is output when allowed.
+    max_rate: Option<Rate>,
+
+    #[structopt(
+        long = "icons",
+        value_name = "SET",
+        default_value = "unicode",
+        raw(possible_values = "&IconSet::variants()")
+    )]
+    /// The set of glyphs to use for the icon helper.
+    icon_set: IconSet,
+
+    #[structopt(long = "icon", value_name = "NAME=GLYPH", raw(number_of_values = "1"))]
+    /// Override a single glyph of the icon set, like "playing=>". Can be given multiple times.
+    icon_overrides: Vec<IconOverride>,
+
+    #[structopt(long = "check", conflicts_with = "watch")]
+    /// Check the template for unknown fields and helpers instead of rendering it. Exits with an
+    /// error if any problems are found.
+    check: bool,
+
+    #[structopt(long = "sample", conflicts_with = "watch")]
+    /// Render the template using built-in sample metadata instead of metadata from a player.
+    sample: bool,
+
+    #[structopt(long = "all-players")]
+    /// Render all players at once. The template gets a "players" list and the selected player as
+    /// "current" instead of the fields of a single player.
+    all_players: bool,
+
+    #[structopt(long = "template-env", value_name = "VAR", raw(number_of_values = "1"))]
+    /// Expose this environment variable to the template as env.VAR. Can be given multiple times.
+    template_env: Vec<String>,
+
+    #[structopt(long = "allow-exec")]
+    /// Allow the template to run external commands using the exec helper.
+    allow_exec: bool,
+
+    #[structopt(long = "exec-timeout", value_name = "MILLISECONDS", default_value = "1000")]
+    /// Fail when a command run by the exec helper takes longer than this.
+    exec_timeout: u64,
+
+    #[structopt(long = "helpers", value_name = "FILE", parse(from_os_str))]
+    /// Load custom helpers from a Rhai script. Every public function in the script becomes a
+    /// helper with the same name.
+    helpers: Option<PathBuf>,
 
     #[structopt(
         name = "FORMAT",
         raw(long_help = "include_str!(\"../format_help.txt\")")
     )]
-    /// The format string. Full reference is available under the --help option.
+    /// The format string, or "@NAME" for a template from the config file. Full reference is
+    /// available under the --help option.
     template: String,
 }
 
-pub(crate) fn run(options: &Options, settings: &Settings) -> Result<(), Error> {
-    let player = settings.find_player()?;
+const DEFAULT_WATCH_INTERVAL: u32 = 250;
 
-    let handlebars = setup_handlebars(&options.template)?;
+impl Options {
+    pub(crate) fn set_default_watch_interval(&mut self, interval: u32) {
+        self.watch_interval = self.watch_interval.or(Some(interval));
+    }
+
+    /// The name of the configured template to use, when given like "@short".
+    pub(crate) fn template_name(&self) -> Option<String> {
+        let name = self.template.strip_prefix('@')?;
+        if name.starts_with('@') {
+            return None;
+        }
+        let is_name = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+
+        if is_name {
+            Some(name.to_string())
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn set_template(&mut self, template: String) {
+        self.template = template;
+    }
+
+    /// Templates that start with "@@" render from the second "@", so that they are not taken for
+    /// the name of a configured template.
+    fn template_source(&self) -> &str {
+        match self.template.strip_prefix("@@") {
+            Some(_) => &self.template[1..],
+            None => &self.template,
+        }
+    }
+}
+
+pub(crate) fn run(options: &Options, settings: &Settings, finder: &Finder) -> Result<(), Error> {
+    let watch_interval = options.watch_interval.unwrap_or(DEFAULT_WATCH_INTERVAL);
+    let (handlebars, helper_names) =
+        setup_handlebars(options.template_source(), options, settings.strict)?;
+    let globals = Globals::new(&options.template_env);
+
+    if options.check {
+        check_template(&handlebars, &helper_names, options, settings)?;
+    }
+    if options.sample {
+        let output = if options.all_players {
+            render_template(&handlebars, &globals, &AllPlayersView::sample())?
+        } else {
+            render_template(&handlebars, &globals, &MetadataView::sample())?
+        };
+        println!("{}", output);
+    }
+    if options.check || options.sample {
+        return Ok(());
+    }
+
+    let mut filter = ChangeFilter::new(
+        &options.on_change,
+        &known_fields(options.all_players)?,
+        Duration::from_millis(options.debounce.unwrap_or(0)),
+        options.max_rate,
+    )?;
+
+    if options.all_players {
+        return if options.watch {
+            all_players::watch(
+                &handlebars,
+                &globals,
+                settings,
+                finder,
+                &mut filter,
+                watch_interval,
+            )
+        } else {
+            let data = all_players::snapshot(finder, settings)?;
+            println!("{}", render_template(&handlebars, &globals, &data)?);
+            Ok(())
+        };
+    }
+
+    let player = settings.find_player(finder)?;
 
     if options.watch {
-        watch_player(player, handlebars, options.watch_interval)?
+        watch_player(
+            player,
+            handlebars,
+            &globals,
+            &mut filter,
+            watch_interval,
+        )?
     } else {
         let metadata = player.get_metadata()?;
         let metadata_view = MetadataView::from_player(&metadata, &player)?;
-        println!("{}", render_template(&handlebars, &metadata_view)?);
+        println!("{}", render_template(&handlebars, &globals, &metadata_view)?);
     }
     Ok(())
 }
 
-fn setup_handlebars(template: &str) -> Result<Handlebars, Error> {
+/// Also returns the names of all helpers, used for suggestions.
+/// Set up the helpers and the template. With `strict`, rendering fails on missing fields.
+fn setup_handlebars(
+    template: &str,
+    options: &Options,
+    strict: bool,
+) -> Result<(Handlebars, Vec<String>), Error> {
     let mut handlebars = Handlebars::new();
+    let mut helper_names: Vec<String> =
+        BUILT_IN_HELPER_NAMES.iter().map(|name| name.to_string()).collect();
+    let mut register = |handlebars: &mut Handlebars, name: &str, helper: Box<dyn HelperDef>| {
+        handlebars.register_helper(name, helper);
+        helper_names.push(name.to_string());
+    };
 
-    handlebars.set_strict_mode(false);
+    handlebars.set_strict_mode(strict);
     handlebars.register_escape_fn(no_escape);
-    handlebars.register_helper("join", Box::new(join::helper));
-    handlebars.register_helper("or", Box::new(or::helper));
-    handlebars.register_helper("time", Box::new(time::helper));
+    register(&mut handlebars, "join", Box::new(join::helper));
+    register(&mut handlebars, "or", Box::new(or::helper));
+    register(&mut handlebars, "time", Box::new(time::helper));
+    register(&mut handlebars, "upper", Box::new(case::upper));
+    register(&mut handlebars, "lower", Box::new(case::lower));
+    register(&mut handlebars, "title-case", Box::new(case::title_case));
+    register(&mut handlebars, "replace", Box::new(replace::helper));
+    register(&mut handlebars, "trim", Box::new(trim::trim));
+    register(&mut handlebars, "strip-suffix", Box::new(trim::strip_suffix));
+    register(&mut handlebars, "first-artist", Box::new(first_artist::helper));
+    register(&mut handlebars, "add", Box::new(Arithmetic(|a, b| a + b)));
+    register(&mut handlebars, "sub", Box::new(Arithmetic(|a, b| a - b)));
+    register(&mut handlebars, "mul", Box::new(Arithmetic(|a, b| a * b)));
+    register(&mut handlebars, "div", Box::new(Arithmetic(|a, b| a / b)));
+    register(&mut handlebars, "round", Box::new(Round));
+    register(&mut handlebars, "percent", Box::new(number::percent));
+    register(&mut handlebars, "format-number", Box::new(number::format_number));
+    register(&mut handlebars, "date", Box::new(date::helper));
+    register(
+        &mut handlebars,
+        "exec",
+        Box::new(match (options.allow_exec, options.watch) {
+            (false, _) => Exec::disabled(),
+            (true, false) => Exec::new(Duration::from_millis(options.exec_timeout)),
+            (true, true) => Exec::watching(Duration::from_millis(options.exec_timeout)),
+        }),
+    );
+    register(
+        &mut handlebars,
+        "icon",
+        Box::new(Icons::new(options.icon_set, &options.icon_overrides)),
+    );
+
+    if let Some(ref path) = options.helpers {
+        for helper in script::load(path)? {
+            let name = helper.name().to_string();
+            if handlebars.get_helper(&name).is_some() {
+                return Err(format_err!(
+                    "Helper \"{}\" in {} has the same name as a built-in helper",
+                    name,
+                    path.display()
+                ));
+            }
+            register(&mut handlebars, &name, Box::new(helper));
+        }
+    }
 
     if let Err(error) = handlebars.register_template_string("main", template) {
         return Err(error.into());
     }
 
-    Ok(handlebars)
+    Ok((handlebars, helper_names))
+}
+
+/// Failures caused by the template rather than by the player.
+#[derive(Debug)]
+pub(crate) enum TemplateFailure {
+    MissingField {
+        field: String,
+        line: usize,
+        column: usize,
+        suggestion: String,
+    },
+}
+
+impl fmt::Display for TemplateFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TemplateFailure::MissingField {
+                field,
+                line,
+                column,
+                suggestion,
+            } => write!(
+                f,
+                "Template uses missing field \"{}\" on line {}, column {}.{}",
+                field, line, column, suggestion
+            ),
+        }
+    }
 }
 
-fn watch_player(player: Player, handlebars: Handlebars, interval: u32) -> Result<(), Error> {
+impl Fail for TemplateFailure {}
+
+/// Whether the error comes from parsing or rendering the template.
+pub(crate) fn is_template_error(error: &Error) -> bool {
+    use self::handlebars::{TemplateError, TemplateRenderError};
+
+    error.downcast_ref::<TemplateFailure>().is_some()
+        || error.downcast_ref::<TemplateError>().is_some()
+        || error.downcast_ref::<RenderError>().is_some()
+        || error.downcast_ref::<TemplateRenderError>().is_some()
+}
+
+impl TemplateFailure {
+    fn from_render_error(error: &RenderError) -> Option<TemplateFailure> {
+        let field = missing_field_name(&error.desc)?;
+        let suggestion = known_fields(false)
+            .ok()
+            .and_then(|fields| {
+                closest_match(&field, fields.iter().map(String::as_str))
+                    .map(|suggestion| format!(" Did you mean \"{}\"?", suggestion))
+            })
+            .unwrap_or_default();
+
+        Some(TemplateFailure::MissingField {
+            field,
+            line: error.line_no.unwrap_or(1),
+            column: error.column_no.unwrap_or(1),
+            suggestion,
+        })
+    }
+}
+
+/// Extract the field name from Handlebars' strict mode errors, which look like
+/// `Variable "field" not found in strict mode.`
+fn missing_field_name(description: &str) -> Option<String> {
+    let quoted = description
+        .trim_start_matches("Variable ")
+        .trim_end_matches(" not found in strict mode.");
+
+    if quoted.len() == description.len() {
+        return None;
+    }
+
+    serde_json::from_str(quoted).ok()
+}
+
+fn check_template(
+    handlebars: &Handlebars,
+    helper_names: &[String],
+    options: &Options,
+    settings: &Settings,
+) -> Result<(), Error> {
+    let template = handlebars
+        .get_template("main")
+        .ok_or_else(|| format_err!("Template was not registered"))?;
+    let problems = check::check_template(
+        template,
+        handlebars,
+        &known_fields(options.all_players)?,
+        &helper_names.iter().map(String::as_str).collect::<Vec<_>>(),
+    );
+
+    for problem in &problems {
+        eprintln!("{}", problem);
+    }
+
+    if problems.is_empty() {
+        if settings.verbose {
+            eprintln!("No problems found in template.");
+        }
+        Ok(())
+    } else {
+        Err(format_err!(
+            "Found {} problem(s) in template",
+            problems.len()
+        ))
+    }
+}
+
+/// Names of all the top level fields that templates can use.
+fn known_fields(all_players: bool) -> Result<Vec<String>, Error> {
+    let globals = Globals::sample();
+    let sample = if all_players {
+        serde_json::to_value(globals.context(&AllPlayersView::sample()))?
+    } else {
+        serde_json::to_value(globals.context(&MetadataView::sample()))?
+    };
+
+    match sample {
+        Value::Object(fields) => Ok(fields.keys().cloned().collect()),
+        _ => Ok(vec![]),
+    }
+}
+
+fn watch_player(
+    player: Player,
+    handlebars: Handlebars,
+    globals: &Globals,
+    filter: &mut ChangeFilter,
+    interval: u32,
+) -> Result<(), Error> {
+    let player_info = PlayerInfo::from_player(&player)?;
     let mut tracker = player.track_progress(interval)?;
-    let mut last_output = String::new();
     loop {
         let progress = tracker.tick();
-        let metadata_view = MetadataView::from_progress(progress.progress)?;
-        let output = render_template(&handlebars, &metadata_view)?;
-        if output != last_output {
+        let metadata_view = MetadataView::from_progress(progress.progress, &player_info)?;
+        let output = render_template(&handlebars, globals, &metadata_view)?;
+        if let Some(output) = filter.offer(output, &metadata_view, Instant::now())? {
             println!("{}", output);
-            last_output = output;
         }
     }
 }
 
-fn render_template(handlebars: &Handlebars, metadata_view: &MetadataView) -> Result<String, Error> {
+fn render_template<T: Serialize>(
+    handlebars: &Handlebars,
+    globals: &Globals,
+    data: &T,
+) -> Result<String, Error> {
     handlebars
-        .render("main", metadata_view)
-        .map_err(|e| e.into())
+        .render("main", &globals.context(data))
+        .map_err(|error| match TemplateFailure::from_render_error(&error) {
+            Some(failure) => failure.into(),
+            None => error.into(),
+        })
+}
+
+/// Handlebars with all built-in helpers, for rendering templates in the tests of each helper.
+#[cfg(test)]
+pub(crate) fn test_handlebars() -> Handlebars {
+    let options = Options::from_iter(vec!["format", ""]);
+    setup_handlebars("", &options, false).expect("built-in helpers").0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_requires_watch_for_watch_options() {
+        assert!(Options::from_iter_safe(vec!["format", "{{title}}"]).is_ok());
+        assert!(Options::from_iter_safe(vec!["format", "--debounce", "500", "{{title}}"]).is_err());
+        assert!(Options::from_iter_safe(vec!["format", "-w", "--debounce", "500", "{{title}}"]).is_ok());
+    }
+
+    #[test]
+    fn it_escapes_templates_starting_with_at() {
+        let options = Options::from_iter(vec!["format", "@short"]);
+        assert_eq!(options.template_name(), Some(String::from("short")));
+
+        let options = Options::from_iter(vec!["format", "@@home"]);
+        assert_eq!(options.template_name(), None);
+        assert_eq!(options.template_source(), "@home");
+    }
+
+    #[test]
+    fn it_registers_all_named_helpers() {
+        let options = Options::from_iter(vec!["format", "{{title}}"]);
+        let (handlebars, helper_names) =
+            setup_handlebars(&options.template, &options, false).unwrap();
+
+        for name in &helper_names {
+            assert!(handlebars.get_helper(name).is_some(), "{} is not registered", name);
+        }
+        assert!(helper_names.contains(&String::from("exec")));
+    }
+
+    #[test]
+    fn it_fails_on_missing_fields_in_strict_mode() {
+        let options = Options::from_iter(vec!["format", "{{title}}\n{{artistString}}"]);
+        let handlebars = setup_handlebars(&options.template, &options, true).unwrap().0;
+
+        let error = render_template(&handlebars, &Globals::sample(), &MetadataView::sample()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Template uses missing field \"artistString\" on line 2, column 1. \
+             Did you mean \"artistsString\"?"
+        );
+        assert!(error.downcast_ref::<TemplateFailure>().is_some());
+    }
+
+    #[test]
+    fn it_extracts_missing_field_names() {
+        assert_eq!(
+            missing_field_name("Variable \"raw.xesam:genre\" not found in strict mode."),
+            Some(String::from("raw.xesam:genre"))
+        );
+        assert_eq!(missing_field_name("Helper not defined: \"foo\""), None);
+    }
+
+    #[test]
+    fn it_knows_all_fields() {
+        let fields = known_fields(false).unwrap();
+
+        assert!(fields.contains(&String::from("artistsString")));
+        assert!(fields.contains(&String::from("positionInSeconds")));
+
+        assert!(fields.contains(&String::from("hostname")));
+
+        let fields = known_fields(true).unwrap();
+        assert_eq!(
+            fields,
+            vec!["current", "env", "hostname", "now", "players"]
+        );
+    }
+
+    #[test]
+    fn it_renders_player_capabilities() {
+        let options = Options::from_iter(vec![
+            "format",
+            "{{identity}}{{#if canGoNext}} ⏭{{/if}} {{join \", \" supportedUriSchemes}}",
+        ]);
+        let handlebars = setup_handlebars(&options.template, &options, false).unwrap().0;
+
+        assert_eq!(
+            render_template(&handlebars, &Globals::sample(), &MetadataView::sample()).unwrap(),
+            "Spotify ⏭ spotify"
+        );
+    }
+
+    #[test]
+    fn it_renders_globals() {
+        let options = Options::from_iter(vec![
+            "format",
+            "{{hostname}} {{env.USER}} {{#if now.epoch}}{{date now.iso format=\"%Y\"}}{{/if}}",
+        ]);
+        let handlebars = setup_handlebars(&options.template, &options, false).unwrap().0;
+
+        let output = render_template(&handlebars, &Globals::sample(), &MetadataView::sample());
+        assert!(output.unwrap().starts_with("workstation mbergmark 20"));
+    }
+
+    #[test]
+    fn it_renders_all_players() {
+        let options = Options::from_iter(vec![
+            "format",
+            "--all-players",
+            "{{#each players}}{{identity}} {{icon playbackStatus}}{{/each}} ({{current.busName}})",
+        ]);
+        let handlebars = setup_handlebars(&options.template, &options, false).unwrap().0;
+
+        assert_eq!(
+            render_template(&handlebars, &Globals::sample(), &AllPlayersView::sample()).unwrap(),
+            "Spotify ▶ (org.mpris.MediaPlayer2.spotify)"
+        );
+    }
 }
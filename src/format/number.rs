@@ -0,0 +1,106 @@
+use super::handlebars::*;
+use super::math::number_of_value;
+
+pub(crate) fn percent(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    if let Some(num) = h.param(0).and_then(|p| number_of_value(p.value())) {
+        let decimals = decimals_param(h);
+        out.write(&format!("{:.*}%", decimals, num * 100.0))?;
+    }
+    Ok(())
+}
+
+pub(crate) fn format_number(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    if let Some(num) = h.param(0).and_then(|p| number_of_value(p.value())) {
+        let decimals = decimals_param(h);
+        out.write(&format!("{:.*}", decimals, num))?;
+    }
+    Ok(())
+}
+
+fn decimals_param(h: &Helper) -> usize {
+    h.param(1)
+        .and_then(|param| param.value().as_u64())
+        .unwrap_or(0)
+        .min(15) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::format::test_handlebars;
+
+    #[test]
+    fn it_renders_percentages() {
+        let handlebars = test_handlebars();
+        let values = json!({"volume": 0.6300000190734863, "autoRating": 0.325});
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{percent volume}}"#, &values)
+                .unwrap(),
+            "63%"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{percent autoRating 1}}"#, &values)
+                .unwrap(),
+            "32.5%"
+        );
+    }
+
+    #[test]
+    fn it_formats_numbers_with_fixed_decimals() {
+        let handlebars = test_handlebars();
+        let values = json!({"playbackRate": 1, "volume": 0.6300000190734863});
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{format-number playbackRate 2}}"#, &values)
+                .unwrap(),
+            "1.00"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{format-number volume}}"#, &values)
+                .unwrap(),
+            "1"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{format-number "0.126" 2}}"#, &values)
+                .unwrap(),
+            "0.13"
+        );
+    }
+
+    #[test]
+    fn it_renders_nothing_on_bad_values() {
+        let handlebars = test_handlebars();
+
+        assert_eq!(
+            handlebars.render_template(r#"{{percent missing}}"#, &()).unwrap(),
+            ""
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{format-number "bad" 2}}"#, &())
+                .unwrap(),
+            ""
+        );
+    }
+}
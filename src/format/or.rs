@@ -5,7 +5,7 @@ pub(crate) fn helper<'reg, 'rc>(
     registry: &'reg Handlebars,
     ctx: &Context,
     rc: &mut RenderContext<'reg>,
-    out: &mut Output,
+    out: &mut dyn Output,
 ) -> HelperResult {
     let first_value = h
         .params()
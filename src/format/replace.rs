@@ -0,0 +1,114 @@
+use super::handlebars::*;
+use super::regex::Regex;
+
+pub(crate) fn helper(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let value = match h.param(0).map(|p| p.value()) {
+        Some(value) if !value.is_null() => value.render(),
+        _ => return Ok(()),
+    };
+
+    let pattern = match h.param(1) {
+        Some(param) => param.value().render(),
+        None => return Err(RenderError::new("replace: Missing pattern to replace")),
+    };
+    let replacement = h
+        .param(2)
+        .map(|param| param.value().render())
+        .unwrap_or_default();
+
+    let use_regex = h
+        .hash_get("regex")
+        .and_then(|param| param.value().as_bool())
+        .unwrap_or(false);
+
+    if use_regex {
+        let regex = Regex::new(&pattern)
+            .map_err(|error| RenderError::new(format!("replace: Invalid regex: {}", error)))?;
+        out.write(&regex.replace_all(&value, replacement.as_str()))?;
+    } else if pattern.is_empty() {
+        out.write(&value)?;
+    } else {
+        out.write(&value.replace(&pattern, &replacement))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::format::test_handlebars;
+
+    #[test]
+    fn it_replaces_literal_strings() {
+        let handlebars = test_handlebars();
+        let values = json!({"title": "Song (feat. Someone) (feat. Someone)"});
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{replace title "feat." "ft."}}"#, &values)
+                .unwrap(),
+            "Song (ft. Someone) (ft. Someone)"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{replace title " (feat. Someone)"}}"#, &values)
+                .unwrap(),
+            "Song"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{replace title ".*" "x"}}"#, &values)
+                .unwrap(),
+            "Song (feat. Someone) (feat. Someone)"
+        );
+    }
+
+    #[test]
+    fn it_replaces_regular_expressions() {
+        let handlebars = test_handlebars();
+        let values = json!({"title": "Song - 2011 Remaster"});
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{replace title " - \\d+ Remaster$" "" regex=true}}"#, &values)
+                .unwrap(),
+            "Song"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{replace title "(\\d+)" "[$1]" regex=true}}"#, &values)
+                .unwrap(),
+            "Song - [2011] Remaster"
+        );
+    }
+
+    #[test]
+    fn it_fails_on_invalid_regex() {
+        let handlebars = test_handlebars();
+
+        assert!(handlebars
+            .render_template(r#"{{replace "value" "(" "" regex=true}}"#, &())
+            .is_err());
+    }
+
+    #[test]
+    fn it_renders_nothing_on_missing_values() {
+        let handlebars = test_handlebars();
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{replace title "a" "b"}}"#, &())
+                .unwrap(),
+            ""
+        );
+    }
+}
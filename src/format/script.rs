@@ -0,0 +1,245 @@
+extern crate rhai;
+
+use self::rhai::serde::{from_dynamic, to_dynamic};
+use self::rhai::{CallFnOptions, Dynamic, Engine, FnAccess, Scope, AST};
+use super::handlebars::*;
+use failure::{format_err, Error};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Helper defined by a function in a Rhai script, like
+///
+///   fn shout(text) { text.to_upper() + "!" }
+///
+/// The parameters of the helper are passed as arguments, and the template's data is bound to
+/// `this`. Scripts cannot reach the file system or other processes, and are stopped when they run
+/// for too long.
+pub(crate) struct ScriptHelper {
+    engine: Arc<Engine>,
+    ast: Arc<AST>,
+    name: String,
+    arity: usize,
+}
+
+impl ScriptHelper {
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Load a script file and create a helper for each of its public functions.
+pub(crate) fn load(path: &Path) -> Result<Vec<ScriptHelper>, Error> {
+    let source = fs::read_to_string(path)
+        .map_err(|error| format_err!("Cannot read helpers from {}: {}", path.display(), error))?;
+
+    compile(&source)
+        .map_err(|error| format_err!("Cannot load helpers from {}: {}", path.display(), error))
+}
+
+fn compile(source: &str) -> Result<Vec<ScriptHelper>, Error> {
+    let engine = Arc::new(sandboxed_engine());
+    let ast = Arc::new(engine.compile(source)?);
+
+    let mut helpers: Vec<ScriptHelper> = Vec::new();
+    for function in ast.iter_functions().filter(|function| function.access == FnAccess::Public) {
+        // A helper is called by name only, so it cannot pick an overload by the number of
+        // parameters.
+        if helpers.iter().any(|helper| helper.name == function.name) {
+            return Err(format_err!(
+                "Function \"{}\" is defined more than once, but overloads are not supported",
+                function.name
+            ));
+        }
+        helpers.push(ScriptHelper {
+            engine: engine.clone(),
+            ast: ast.clone(),
+            name: function.name.to_string(),
+            arity: function.params.len(),
+        });
+    }
+    Ok(helpers)
+}
+
+fn sandboxed_engine() -> Engine {
+    let mut engine = Engine::new();
+
+    engine
+        .set_max_operations(100_000)
+        .set_max_call_levels(32)
+        .set_max_expr_depths(64, 32)
+        .set_max_string_size(64 * 1024)
+        .set_max_array_size(10_000)
+        .set_max_map_size(10_000)
+        .disable_symbol("eval");
+
+    // Keep stdout for the rendered template.
+    engine.on_print(|text| eprintln!("{}", text));
+    engine.on_debug(|text, _, position| eprintln!("{:?}: {}", position, text));
+
+    engine
+}
+
+impl HelperDef for ScriptHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Handlebars,
+        ctx: &'rc Context,
+        _: &mut RenderContext<'reg>,
+    ) -> Result<Option<ScopedJson<'reg, 'rc>>, RenderError> {
+        let error = |message: String| RenderError::new(format!("{}: {}", self.name, message));
+
+        if h.params().len() > self.arity {
+            return Err(error(format!(
+                "Expected at most {} parameter(s), got {}",
+                self.arity,
+                h.params().len()
+            )));
+        }
+
+        // Missing parameters are passed as (), like missing fields are null in templates.
+        let mut args = h
+            .params()
+            .iter()
+            .map(|param| to_dynamic(param.value()))
+            .collect::<Result<Vec<Dynamic>, _>>()
+            .map_err(|e| error(e.to_string()))?;
+        args.resize(self.arity, Dynamic::UNIT);
+
+        let mut this = to_dynamic(ctx.data()).map_err(|e| error(e.to_string()))?;
+        let options = CallFnOptions::new()
+            .eval_ast(false)
+            .bind_this_ptr(&mut this);
+
+        let result: Dynamic = self
+            .engine
+            .call_fn_with_options(options, &mut Scope::new(), &self.ast, &self.name, args)
+            .map_err(|e| error(e.to_string()))?;
+        let value: Value = from_dynamic(&result).map_err(|e| error(e.to_string()))?;
+
+        Ok(Some(ScopedJson::Derived(value)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::test_handlebars;
+
+    /// The built-in helpers along with the functions of the script.
+    fn handlebars(source: &str) -> Handlebars {
+        let mut handlebars = test_handlebars();
+        for helper in compile(source).unwrap() {
+            let name = helper.name().to_string();
+            handlebars.register_helper(&name, Box::new(helper));
+        }
+        handlebars
+    }
+
+    #[test]
+    fn it_calls_script_functions() {
+        let handlebars = handlebars(
+            r#"
+            fn shout(text) { text.to_upper() + "!" }
+            fn remaining(position, length) { length - position }
+            "#,
+        );
+        let values = json!({
+            "title": "In Amber Clad",
+            "positionInSeconds": 132,
+            "lengthInSeconds": 230,
+        });
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{shout title}}"#, &values)
+                .unwrap(),
+            "IN AMBER CLAD!"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(
+                    r#"{{time (remaining positionInSeconds lengthInSeconds)}}"#,
+                    &values
+                )
+                .unwrap(),
+            "01:38"
+        );
+    }
+
+    #[test]
+    fn it_binds_the_template_data_to_this() {
+        let handlebars = handlebars(
+            r#"
+            fn now_playing(separator) {
+                if this.isPlaying { this.artists[0] + separator + this.title } else { "" }
+            }
+            "#,
+        );
+        let values = json!({
+            "title": "In Amber Clad",
+            "artists": ["Martin O'Donnell", "Michael Salvatori"],
+            "isPlaying": true,
+        });
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{now_playing " - "}}"#, &values)
+                .unwrap(),
+            "Martin O'Donnell - In Amber Clad"
+        );
+    }
+
+    #[test]
+    fn it_passes_missing_parameters_as_unit() {
+        let handlebars = handlebars(
+            r#"fn fallback(text, other) { if other == () { text } else { other } }"#,
+        );
+        let values = json!({"title": "x"});
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{fallback title "y"}} {{fallback title}}"#, &values)
+                .unwrap(),
+            "y x"
+        );
+        assert!(handlebars
+            .render_template(r#"{{fallback 1 2 3}}"#, &())
+            .is_err());
+    }
+
+    #[test]
+    fn it_only_registers_public_functions() {
+        let helpers = compile(
+            r#"
+            private fn pad(text) { " " + text + " " }
+            fn padded(text) { pad(text) }
+            "#,
+        )
+        .unwrap();
+
+        let names: Vec<&str> = helpers.iter().map(ScriptHelper::name).collect();
+        assert_eq!(names, vec!["padded"]);
+    }
+
+    #[test]
+    fn it_sandboxes_scripts() {
+        let handlebars = handlebars("fn forever(x) { loop {} }");
+        assert!(handlebars.render_template(r#"{{forever 1}}"#, &()).is_err());
+
+        assert!(compile(r#"fn evil() { eval("40 + 2") }"#).is_err());
+    }
+
+    #[test]
+    fn it_reports_syntax_errors() {
+        assert!(compile("fn broken( {").is_err());
+
+        match compile("fn pad(text) { text } fn pad(text, width) { text }") {
+            Err(error) => assert!(error.to_string().contains("overloads are not supported")),
+            Ok(_) => panic!("Overloads should be rejected"),
+        }
+    }
+}
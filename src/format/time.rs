@@ -1,4 +1,5 @@
 use super::handlebars::*;
+use super::math::number_of_value;
 use serde_json::Value;
 
 pub(crate) fn helper(
@@ -6,65 +7,302 @@ pub(crate) fn helper(
     _: &Handlebars,
     _: &Context,
     _: &mut RenderContext,
-    out: &mut Output,
+    out: &mut dyn Output,
 ) -> HelperResult {
+    let unit = match h.hash_get("unit") {
+        Some(param) => Unit::from_value(param.value())?,
+        None => Unit::Seconds,
+    };
+
     if let Some(time) = h.param(0).map(|p| p.value()) {
+        let mut microseconds = match unit.microseconds_of_value(time) {
+            Some(microseconds) => microseconds,
+            None => return Ok(()),
+        };
+
+        // Render the time left until another time, like the remaining time of the media.
+        if let Some(until) = h.hash_get("until") {
+            match unit.microseconds_of_value(until.value()) {
+                Some(end) => {
+                    microseconds = microseconds.checked_sub(end).ok_or_else(|| {
+                        RenderError::new(format!(
+                            "time: The time until {} is out of range",
+                            until.value()
+                        ))
+                    })?
+                }
+                None => return Ok(()),
+            }
+        }
+
+        let duration = Duration::from_microseconds(microseconds);
+
+        if let Some(format) = h.hash_get("format") {
+            out.write(&duration.format(&format.value().render()))?;
+            return Ok(());
+        }
+
+        let style = match h.hash_get("style") {
+            Some(param) => Style::from_value(param.value())?,
+            None => Style::Clock,
+        };
+
         // What width do the user want?
         let width = match h.param(1) {
-            Some(param) => width_of_value(param.value()),
-            None => width_of_value(time),
+            Some(param) => width_of_value(param.value(), unit),
+            None => width_of_value(time, unit),
         };
 
-        if let Some(seconds) = time.as_u64() {
-            return render_time(seconds, width, out);
-        }
+        out.write(&match style {
+            Style::Clock => duration.clock(width.without_days()),
+            Style::Days => duration.clock(width),
+            Style::Short => duration.humanize_short(),
+            Style::Long => duration.humanize_long(),
+        })?;
     }
     Ok(())
 }
 
-use std::u64::MAX as u64_max;
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Unit {
+    Seconds,
+    Milliseconds,
+    Microseconds,
+}
+
+impl Unit {
+    fn from_value(val: &Value) -> Result<Unit, RenderError> {
+        match val.as_str() {
+            Some("s") | Some("seconds") => Ok(Unit::Seconds),
+            Some("ms") | Some("milliseconds") => Ok(Unit::Milliseconds),
+            Some("us") | Some("µs") | Some("microseconds") => Ok(Unit::Microseconds),
+            _ => Err(RenderError::new(format!(
+                "time: Unknown unit {}. Valid units are \"seconds\", \"milliseconds\" and \"microseconds\"",
+                val
+            ))),
+        }
+    }
+
+    fn microseconds_of_value(self, val: &Value) -> Option<i64> {
+        let factor = match self {
+            Unit::Seconds => 1_000_000.0,
+            Unit::Milliseconds => 1_000.0,
+            Unit::Microseconds => 1.0,
+        };
+
+        number_of_value(val)
+            .map(|time| (time * factor).round())
+            .filter(|microseconds| microseconds.abs() < i64::MAX as f64)
+            .map(|microseconds| microseconds as i64)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Style {
+    Clock,
+    /// Like clock, but with days for times of a day or longer.
+    Days,
+    Short,
+    Long,
+}
+
+impl Style {
+    fn from_value(val: &Value) -> Result<Style, RenderError> {
+        match val.as_str() {
+            Some("clock") => Ok(Style::Clock),
+            Some("days") => Ok(Style::Days),
+            Some("short") => Ok(Style::Short),
+            Some("long") => Ok(Style::Long),
+            _ => Err(RenderError::new(format!(
+                "time: Unknown style {}. Valid styles are \"clock\", \"days\", \"short\" and \"long\"",
+                val
+            ))),
+        }
+    }
+}
+
 const MINUTE: u64 = 60;
 const HOUR: u64 = 60 * MINUTE;
+const DAY: u64 = 24 * HOUR;
 
+#[derive(Debug, PartialEq)]
 enum Width {
     Minute,
     Hour,
+    Day,
     Invalid,
 }
 
-fn width_of_value(val: &Value) -> Width {
+impl Width {
+    fn without_days(self) -> Width {
+        match self {
+            Width::Day => Width::Hour,
+            width => width,
+        }
+    }
+}
+
+fn width_of_value(val: &Value, unit: Unit) -> Width {
     match val {
-        Value::Number(ref num) => match num.as_u64() {
-            Some(0...HOUR) => Width::Minute,
-            Some(HOUR...u64_max) => Width::Hour,
-            _ => Width::Invalid,
+        Value::String(ref s) if s == "hour" || s == "hours" => Width::Hour,
+        Value::String(ref s) if s == "minute" || s == "minutes" => Width::Minute,
+        Value::Number(_) => match unit.microseconds_of_value(val) {
+            Some(microseconds) => match microseconds.unsigned_abs() / 1_000_000 {
+                seconds if seconds < HOUR => Width::Minute,
+                seconds if seconds < DAY => Width::Hour,
+                _ => Width::Day,
+            },
+            None => Width::Invalid,
         },
-        Value::String(ref s) if s == "hour" => Width::Hour,
-        Value::String(ref s) if s == "minute" => Width::Minute,
         _ => Width::Invalid,
     }
 }
 
-fn render_time(seconds: u64, width: Width, out: &mut Output) -> HelperResult {
-    let mut seconds = seconds;
+/// A possibly negative duration, split up into its components.
+#[derive(Debug, PartialEq)]
+struct Duration {
+    negative: bool,
+    total_seconds: u64,
+    microseconds: u64,
+}
 
-    let whole_hours = seconds / HOUR;
-    seconds -= whole_hours * HOUR;
+impl Duration {
+    fn from_microseconds(microseconds: i64) -> Duration {
+        let absolute = microseconds.unsigned_abs();
+        Duration {
+            negative: microseconds < 0,
+            total_seconds: absolute / 1_000_000,
+            microseconds: absolute % 1_000_000,
+        }
+    }
+
+    fn days(&self) -> u64 {
+        self.total_seconds / DAY
+    }
 
-    let whole_minutes = seconds / MINUTE;
-    seconds -= whole_minutes * MINUTE;
+    fn hours(&self) -> u64 {
+        (self.total_seconds % DAY) / HOUR
+    }
 
-    match width {
-        Width::Hour => out.write(&format!(
-            "{:02}:{:02}:{:02}",
-            whole_hours, whole_minutes, seconds
-        ))?,
-        Width::Minute | Width::Invalid => {
-            out.write(&format!("{:02}:{:02}", whole_minutes, seconds))?
+    fn minutes(&self) -> u64 {
+        (self.total_seconds % HOUR) / MINUTE
+    }
+
+    fn seconds(&self) -> u64 {
+        self.total_seconds % MINUTE
+    }
+
+    /// The sign of renderings in whole seconds, which don't render "-00:00" for times that round
+    /// down to zero.
+    fn sign(&self) -> &'static str {
+        if self.negative && self.total_seconds > 0 {
+            "-"
+        } else {
+            ""
         }
     }
 
-    Ok(())
+    fn clock(&self, width: Width) -> String {
+        match width {
+            Width::Day => format!(
+                "{}{}d {:02}:{:02}:{:02}",
+                self.sign(),
+                self.days(),
+                self.hours(),
+                self.minutes(),
+                self.seconds()
+            ),
+            Width::Hour => format!(
+                "{}{:02}:{:02}:{:02}",
+                self.sign(),
+                self.total_seconds / HOUR,
+                self.minutes(),
+                self.seconds()
+            ),
+            Width::Minute | Width::Invalid => format!(
+                "{}{:02}:{:02}",
+                self.sign(),
+                self.minutes(),
+                self.seconds()
+            ),
+        }
+    }
+
+    fn parts(&self) -> Vec<(u64, &'static str, &'static str)> {
+        vec![
+            (self.days(), "d", "day"),
+            (self.hours(), "h", "hour"),
+            (self.minutes(), "m", "minute"),
+            (self.seconds(), "s", "second"),
+        ]
+    }
+
+    /// Renders like "1h 3m 20s".
+    fn humanize_short(&self) -> String {
+        let parts: Vec<String> = self
+            .parts()
+            .into_iter()
+            .filter(|&(amount, _, _)| amount > 0)
+            .map(|(amount, suffix, _)| format!("{}{}", amount, suffix))
+            .collect();
+
+        if parts.is_empty() {
+            String::from("0s")
+        } else {
+            format!("{}{}", self.sign(), parts.join(" "))
+        }
+    }
+
+    /// Renders like "1 hour 3 minutes 20 seconds".
+    fn humanize_long(&self) -> String {
+        let parts: Vec<String> = self
+            .parts()
+            .into_iter()
+            .filter(|&(amount, _, _)| amount > 0)
+            .map(|(amount, _, name)| {
+                format!("{} {}{}", amount, name, if amount == 1 { "" } else { "s" })
+            })
+            .collect();
+
+        if parts.is_empty() {
+            String::from("0 seconds")
+        } else {
+            format!("{}{}", self.sign(), parts.join(" "))
+        }
+    }
+
+    /// Renders using a format string. See the format help for the supported specifiers.
+    fn format(&self, format: &str) -> String {
+        let shows_fraction = format.contains("%f") && self.microseconds >= 1000;
+        let mut result = String::from(if self.negative && shows_fraction { "-" } else { self.sign() });
+        let mut chars = format.chars();
+
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                result.push(c);
+                continue;
+            }
+
+            match chars.next() {
+                Some('d') => result.push_str(&self.days().to_string()),
+                Some('H') => result.push_str(&format!("{:02}", self.hours())),
+                Some('M') => result.push_str(&format!("{:02}", self.minutes())),
+                Some('S') => result.push_str(&format!("{:02}", self.seconds())),
+                Some('f') => result.push_str(&format!("{:03}", self.microseconds / 1000)),
+                Some('h') => result.push_str(&(self.total_seconds / HOUR).to_string()),
+                Some('m') => result.push_str(&(self.total_seconds / MINUTE).to_string()),
+                Some('s') => result.push_str(&self.total_seconds.to_string()),
+                Some('%') => result.push('%'),
+                Some(other) => {
+                    result.push('%');
+                    result.push(other);
+                }
+                None => result.push('%'),
+            }
+        }
+
+        result
+    }
 }
 
 #[cfg(test)]
@@ -190,6 +428,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_renders_other_units() {
+        let mut handlebars = Handlebars::new();
+        handlebars.register_helper("time", Box::new(helper));
+
+        let values = json!({
+            "position": 34_500_000,
+            "length": ((2 * 60 * 60) + (5 * 60) + 34) * 1_000_000_u64,
+        });
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{time position unit="microseconds"}}"#, &values)
+                .unwrap(),
+            "00:34"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{time position length unit="us"}}"#, &values)
+                .unwrap(),
+            "00:00:34"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{time 61999 unit="ms"}}"#, &values)
+                .unwrap(),
+            "01:01"
+        );
+
+        assert!(handlebars
+            .render_template(r#"{{time position unit="fortnights"}}"#, &values)
+            .is_err());
+    }
+
     #[test]
     fn it_renders_nothing_when_value_is_bad() {
         let mut handlebars = Handlebars::new();
@@ -203,13 +477,180 @@ mod tests {
         );
 
         assert_eq!(
-            handlebars.render_template(r#"{{time -100}}"#, &()).unwrap(),
+            handlebars.render_template(r#"{{time NaN}}"#, &()).unwrap(),
             ""
         );
+    }
+
+    #[test]
+    fn it_renders_negative_and_remaining_time() {
+        let mut handlebars = Handlebars::new();
+        handlebars.register_helper("time", Box::new(helper));
+
+        let values = json!({
+            "position": 86,
+            "length": 220,
+        });
 
         assert_eq!(
-            handlebars.render_template(r#"{{time NaN}}"#, &()).unwrap(),
+            handlebars.render_template(r#"{{time -134}}"#, &()).unwrap(),
+            "-02:14"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{time position until=length}}"#, &values)
+                .unwrap(),
+            "-02:14"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{time position "hour" until=length}}"#, &values)
+                .unwrap(),
+            "-00:02:14"
+        );
+
+        assert_eq!(
+            handlebars.render_template(r#"{{time -0.5}}"#, &()).unwrap(),
+            "00:00"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{time -0.5 format="%M:%S.%f"}}"#, &())
+                .unwrap(),
+            "-00:00.500"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{time position until=missing}}"#, &values)
+                .unwrap(),
             ""
         );
+
+        assert!(handlebars
+            .render_template(r#"{{time 9000000000000 until=-9000000000000}}"#, &())
+            .is_err());
+    }
+
+    #[test]
+    fn it_renders_days() {
+        let mut handlebars = Handlebars::new();
+        handlebars.register_helper("time", Box::new(helper));
+
+        let values = json!({
+            "audiobook": (26 * 60 * 60) + (5 * 60) + 34,
+            "short": 34,
+        });
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{time audiobook}}"#, &values)
+                .unwrap(),
+            "26:05:34"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{time audiobook style="days"}}"#, &values)
+                .unwrap(),
+            "1d 02:05:34"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{time short audiobook style="days"}}"#, &values)
+                .unwrap(),
+            "0d 00:00:34"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{time short style="days"}}"#, &values)
+                .unwrap(),
+            "00:34"
+        );
+    }
+
+    #[test]
+    fn it_renders_humanized_time() {
+        let mut handlebars = Handlebars::new();
+        handlebars.register_helper("time", Box::new(helper));
+
+        let values = json!({
+            "long": (60 * 60) + (2 * 60),
+            "middle": (3 * 60) + 20,
+            "short": 1,
+            "zero": 0,
+        });
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{time middle style="short"}}"#, &values)
+                .unwrap(),
+            "3m 20s"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{time long style="long"}}"#, &values)
+                .unwrap(),
+            "1 hour 2 minutes"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{time short style="long"}}"#, &values)
+                .unwrap(),
+            "1 second"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{time zero style="short"}}"#, &values)
+                .unwrap(),
+            "0s"
+        );
+
+        assert!(handlebars
+            .render_template(r#"{{time zero style="fancy"}}"#, &values)
+            .is_err());
+    }
+
+    #[test]
+    fn it_renders_with_format_strings() {
+        let mut handlebars = Handlebars::new();
+        handlebars.register_helper("time", Box::new(helper));
+
+        let values = json!({
+            "position": 200_500_000,
+            "audiobook": (26 * 60 * 60) + (5 * 60) + 34,
+        });
+
+        assert_eq!(
+            handlebars
+                .render_template(
+                    r#"{{time position format="%M:%S.%f" unit="microseconds"}}"#,
+                    &values
+                )
+                .unwrap(),
+            "03:20.500"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{time audiobook format="%dd%Hh, %h:%M (%s%%)"}}"#, &values)
+                .unwrap(),
+            "1d02h, 26:05 (93934%)"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{time -90 format="%m min %q"}}"#, &values)
+                .unwrap(),
+            "-1 min %q"
+        );
     }
 }
@@ -0,0 +1,96 @@
+use super::handlebars::*;
+
+pub(crate) fn trim(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    if let Some(value) = h.param(0).map(|p| p.value()) {
+        out.write(value.render().trim())?;
+    }
+    Ok(())
+}
+
+pub(crate) fn strip_suffix(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    if let Some(value) = h.param(0).map(|p| p.value()) {
+        let value = value.render();
+
+        // Strip the first suffix that matches; later suffixes are fallbacks.
+        let stripped = h.params()[1..]
+            .iter()
+            .map(|param| param.value().render())
+            .filter(|suffix| !suffix.is_empty())
+            .find_map(|suffix| value.strip_suffix(suffix.as_str()).map(String::from));
+
+        out.write(stripped.as_ref().unwrap_or(&value))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::format::test_handlebars;
+
+    #[test]
+    fn it_trims_whitespace() {
+        let handlebars = test_handlebars();
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"[{{trim "  padded title "}}]"#, &())
+                .unwrap(),
+            "[padded title]"
+        );
+    }
+
+    #[test]
+    fn it_strips_the_first_matching_suffix() {
+        let handlebars = test_handlebars();
+        let values = json!({"title": "Here Comes the Sun - Remastered 2009"});
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{strip-suffix title " - Remastered 2009"}}"#, &values)
+                .unwrap(),
+            "Here Comes the Sun"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(
+                    r#"{{strip-suffix title " (Live)" " - Remastered 2009" "2009"}}"#,
+                    &values
+                )
+                .unwrap(),
+            "Here Comes the Sun"
+        );
+
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{strip-suffix title " (Live)"}}"#, &values)
+                .unwrap(),
+            "Here Comes the Sun - Remastered 2009"
+        );
+    }
+
+    #[test]
+    fn it_renders_nothing_on_missing_values() {
+        let handlebars = test_handlebars();
+
+        assert_eq!(handlebars.render_template(r#"{{trim}}"#, &()).unwrap(), "");
+        assert_eq!(
+            handlebars
+                .render_template(r#"{{strip-suffix title "x"}}"#, &())
+                .unwrap(),
+            ""
+        );
+    }
+}
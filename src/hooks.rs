@@ -0,0 +1,573 @@
+extern crate mpris;
+
+use super::{Error, Settings};
+use crate::bus::Finder;
+use crate::metadata::{track_of, MetadataView};
+use mpris::{PlaybackStatus, Player};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::io::Write;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use structopt::StructOpt;
+
+/// Position jumps larger than this, compared to the expected position, are reported as seeks.
+const SEEK_THRESHOLD: Duration = Duration::from_secs(1);
+
+#[derive(StructOpt, Debug)]
+pub struct Options {
+    #[structopt(
+        name = "EVENT",
+        raw(
+            possible_values = "&HookEvent::variants()",
+            use_delimiter = "true",
+            required = "true"
+        )
+    )]
+    /// The events to run the command on, separated by commas.
+    events: Vec<HookEvent>,
+
+    #[structopt(long = "json")]
+    /// Write the event and the metadata of the player as JSON to the command's stdin.
+    json: bool,
+
+    #[structopt(
+        long = "concurrency",
+        default_value = "queue",
+        raw(possible_values = "&Concurrency::variants()")
+    )]
+    /// What to do with events that happen while the command is still running: run the command
+    /// again when it finishes ("queue"), ignore them ("drop") or kill the command and run it again
+    /// ("restart").
+    concurrency: Concurrency,
+
+    #[structopt(long = "interval", value_name = "MILLISECONDS", default_value = "250")]
+    /// Check players for changes at this interval.
+    interval: u32,
+
+    #[structopt(name = "COMMAND", raw(required = "true", last = "true"))]
+    /// The command to run, after a "--". Metadata of the player is available in MPRIS_*
+    /// environment variables, like MPRIS_EVENT, MPRIS_TITLE and MPRIS_ARTISTS_STRING.
+    command: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum HookEvent {
+    TrackChange,
+    Play,
+    Pause,
+    Stop,
+    Seek,
+    Volume,
+    PlayerAppeared,
+    PlayerVanished,
+}
+
+impl HookEvent {
+    fn variants() -> [&'static str; 8] {
+        [
+            "track-change",
+            "play",
+            "pause",
+            "stop",
+            "seek",
+            "volume",
+            "player-appeared",
+            "player-vanished",
+        ]
+    }
+
+    fn name(self) -> &'static str {
+        use self::HookEvent::*;
+
+        match self {
+            TrackChange => "track-change",
+            Play => "play",
+            Pause => "pause",
+            Stop => "stop",
+            Seek => "seek",
+            Volume => "volume",
+            PlayerAppeared => "player-appeared",
+            PlayerVanished => "player-vanished",
+        }
+    }
+}
+
+impl std::str::FromStr for HookEvent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use self::HookEvent::*;
+
+        match caseless::default_case_fold_str(s).as_str() {
+            "track-change" => Ok(TrackChange),
+            "play" => Ok(Play),
+            "pause" => Ok(Pause),
+            "stop" => Ok(Stop),
+            "seek" => Ok(Seek),
+            "volume" => Ok(Volume),
+            "player-appeared" => Ok(PlayerAppeared),
+            "player-vanished" => Ok(PlayerVanished),
+            _ => Err(format!("\"{}\" is not a valid event", s)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Concurrency {
+    Queue,
+    Drop,
+    Restart,
+}
+
+impl Concurrency {
+    fn variants() -> [&'static str; 3] {
+        ["queue", "drop", "restart"]
+    }
+}
+
+impl std::str::FromStr for Concurrency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match caseless::default_case_fold_str(s).as_str() {
+            "queue" => Ok(Concurrency::Queue),
+            "drop" => Ok(Concurrency::Drop),
+            "restart" => Ok(Concurrency::Restart),
+            _ => Err(format!("\"{}\" is not a valid concurrency mode", s)),
+        }
+    }
+}
+
+/// The parts of a player that events are detected from. Players that do not have a volume or a
+/// position never send volume or seek events.
+#[derive(Debug, Clone)]
+struct PlayerState {
+    identity: String,
+    bus_name: String,
+    track: Option<String>,
+    status: PlaybackStatus,
+    volume: Option<f64>,
+    position: Option<Duration>,
+    rate: f64,
+    read_at: Instant,
+}
+
+impl PlayerState {
+    fn read(player: &Player) -> Result<PlayerState, Error> {
+        Ok(PlayerState {
+            identity: player.identity().to_string(),
+            bus_name: player.bus_name().to_string(),
+            track: track_of(&player.get_metadata()?),
+            status: player.get_playback_status()?,
+            volume: player.get_volume().ok(),
+            position: player.get_position().ok(),
+            rate: player.get_playback_rate().unwrap_or(1.0),
+            read_at: Instant::now(),
+        })
+    }
+
+    /// Events that happened between the `previous` state and this one.
+    fn events_since(&self, previous: &PlayerState) -> Vec<HookEvent> {
+        let mut events = Vec::new();
+
+        if self.track != previous.track {
+            events.push(HookEvent::TrackChange);
+        } else if self.has_seeked_since(previous) {
+            events.push(HookEvent::Seek);
+        }
+
+        if self.status != previous.status {
+            events.push(match self.status {
+                PlaybackStatus::Playing => HookEvent::Play,
+                PlaybackStatus::Paused => HookEvent::Pause,
+                PlaybackStatus::Stopped => HookEvent::Stop,
+            });
+        }
+
+        if let (Some(volume), Some(previous_volume)) = (self.volume, previous.volume) {
+            if (volume - previous_volume).abs() > f64::EPSILON {
+                events.push(HookEvent::Volume);
+            }
+        }
+
+        events
+    }
+
+    fn has_seeked_since(&self, previous: &PlayerState) -> bool {
+        let (position, mut expected) = match (self.position, previous.position) {
+            (Some(position), Some(previous_position)) => (position, previous_position),
+            _ => return false,
+        };
+        if previous.status == PlaybackStatus::Playing && previous.rate > 0.0 {
+            let elapsed = self.read_at.duration_since(previous.read_at);
+            expected += elapsed.mul_f64(previous.rate);
+        }
+
+        position.abs_diff(expected) > SEEK_THRESHOLD
+    }
+}
+
+/// Runs until killed. Failures while checking players or running the command are reported, and
+/// checking goes on at the next interval.
+pub(crate) fn run(options: &Options, settings: &Settings, finder: &Finder) -> Result<(), Error> {
+    let mut runner = Runner::new(options, settings.verbose);
+    settings.warn_when_not_running(finder);
+    let players = settings.find_selected_players(finder)?;
+    let mut states = read_states(&players, &HashMap::new(), settings.verbose);
+
+    loop {
+        thread::sleep(Duration::from_millis(u64::from(options.interval)));
+
+        let players = match settings.find_selected_players(finder) {
+            Ok(players) => players,
+            Err(error) => {
+                eprintln!("Cannot check players: {}", error);
+                continue;
+            }
+        };
+        let new_states = read_states(&players, &states, settings.verbose);
+
+        for player in &players {
+            let state = match new_states.get(player.unique_name()) {
+                Some(state) => state,
+                None => continue,
+            };
+            let events = match states.get(player.unique_name()) {
+                Some(previous) => state.events_since(previous),
+                None => vec![HookEvent::PlayerAppeared],
+            };
+            for event in events.into_iter().filter(|e| options.events.contains(e)) {
+                let result = player_payload(event, player).and_then(|payload| runner.trigger(payload));
+                if let Err(error) = result {
+                    eprintln!("Cannot run {} for {}: {}", options.command[0], event.name(), error);
+                }
+            }
+        }
+
+        // Only players that left the bus are reported as vanished, as the others keep their state.
+        if options.events.contains(&HookEvent::PlayerVanished) {
+            for (unique_name, state) in &states {
+                if !new_states.contains_key(unique_name) {
+                    if let Err(error) = runner.trigger(vanished_payload(state)) {
+                        eprintln!("Cannot run {} for player-vanished: {}", options.command[0], error);
+                    }
+                }
+            }
+        }
+
+        states = new_states;
+        if let Err(error) = runner.poll() {
+            eprintln!("Cannot run {}: {}", options.command[0], error);
+        }
+    }
+}
+
+fn read_states(
+    players: &[Player],
+    previous: &HashMap<String, PlayerState>,
+    is_verbose: bool,
+) -> HashMap<String, PlayerState> {
+    players
+        .iter()
+        .filter_map(|player| {
+            let state = match PlayerState::read(player) {
+                Ok(state) => Some(state),
+                Err(error) => {
+                    if is_verbose {
+                        eprintln!("Cannot read {}: {}", player.identity(), error);
+                    }
+                    None
+                }
+            };
+            let unique_name = player.unique_name();
+            state_or_previous(state, previous.get(unique_name))
+                .map(|state| (unique_name.to_string(), state))
+        })
+        .collect()
+}
+
+/// A player that cannot be read keeps its previous state, so that a failed read is not mistaken
+/// for the player quitting and starting again.
+fn state_or_previous(state: Option<PlayerState>, previous: Option<&PlayerState>) -> Option<PlayerState> {
+    state.or_else(|| previous.cloned())
+}
+
+/// The event together with all fields of the player, as an object.
+fn player_payload(event: HookEvent, player: &Player) -> Result<Value, Error> {
+    let metadata = player.get_metadata()?;
+    let view = MetadataView::from_player(&metadata, player)?;
+
+    let mut payload = serde_json::to_value(view)?;
+    payload["event"] = Value::from(event.name());
+    Ok(payload)
+}
+
+/// Vanished players cannot be asked for metadata, so only what is remembered about them is known.
+fn vanished_payload(state: &PlayerState) -> Value {
+    json_object(&[
+        ("event", Value::from(HookEvent::PlayerVanished.name())),
+        ("identity", Value::from(state.identity.as_str())),
+        ("busName", Value::from(state.bus_name.as_str())),
+    ])
+}
+
+fn json_object(fields: &[(&str, Value)]) -> Value {
+    Value::Object(
+        fields
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect(),
+    )
+}
+
+/// Environment variables for the fields of a payload, like `MPRIS_ARTISTS_STRING` for
+/// `artistsString`. Missing fields and objects are left out; lists are joined with ", ".
+fn environment_of(payload: &Value) -> Vec<(String, String)> {
+    let fields = match payload.as_object() {
+        Some(fields) => fields,
+        None => return vec![],
+    };
+
+    fields
+        .iter()
+        .filter_map(|(key, value)| {
+            let value = match value {
+                Value::String(text) => text.clone(),
+                Value::Number(_) | Value::Bool(_) => value.to_string(),
+                Value::Array(items) => items
+                    .iter()
+                    .map(|item| match item {
+                        Value::String(text) => text.clone(),
+                        other => other.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                Value::Null | Value::Object(_) => return None,
+            };
+            Some((environment_name(key), value))
+        })
+        .collect()
+}
+
+fn environment_name(field: &str) -> String {
+    let mut name = String::from("MPRIS_");
+    for c in field.chars() {
+        if c.is_uppercase() {
+            name.push('_');
+        }
+        name.extend(c.to_uppercase());
+    }
+    name
+}
+
+/// Runs the hook command, keeping track of the running command according to the concurrency mode.
+struct Runner<'a> {
+    options: &'a Options,
+    is_verbose: bool,
+    running: Option<Child>,
+    queue: VecDeque<Value>,
+}
+
+impl<'a> Runner<'a> {
+    fn new(options: &'a Options, is_verbose: bool) -> Runner<'a> {
+        Runner {
+            options,
+            is_verbose,
+            running: None,
+            queue: VecDeque::new(),
+        }
+    }
+
+    fn trigger(&mut self, payload: Value) -> Result<(), Error> {
+        self.poll()?;
+
+        if self.running.is_none() {
+            return self.start(&payload);
+        }
+
+        match self.options.concurrency {
+            Concurrency::Queue => self.queue.push_back(payload),
+            Concurrency::Drop => {
+                if self.is_verbose {
+                    eprintln!("Dropped {} as the command is still running", payload["event"]);
+                }
+            }
+            Concurrency::Restart => {
+                if let Some(mut child) = self.running.take() {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                }
+                self.start(&payload)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reap a finished command, and start the next queued one.
+    fn poll(&mut self) -> Result<(), Error> {
+        if let Some(ref mut child) = self.running {
+            match child.try_wait()? {
+                Some(status) => {
+                    if !status.success() {
+                        eprintln!("{} exited with {}", self.options.command[0], status);
+                    }
+                }
+                None => return Ok(()),
+            }
+        }
+        self.running = None;
+
+        match self.queue.pop_front() {
+            Some(payload) => self.start(&payload),
+            None => Ok(()),
+        }
+    }
+
+    fn start(&mut self, payload: &Value) -> Result<(), Error> {
+        if self.is_verbose {
+            eprintln!(
+                "Running {} for {} on {}",
+                self.options.command[0], payload["event"], payload["identity"]
+            );
+        }
+
+        let mut child = Command::new(&self.options.command[0])
+            .args(&self.options.command[1..])
+            .envs(environment_of(payload))
+            .stdin(if self.options.json {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .spawn()?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            let json = serde_json::to_string(payload)?;
+            // Commands that do not read their input must not block us.
+            thread::spawn(move || stdin.write_all(json.as_bytes()));
+        }
+
+        self.running = Some(child);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(track: &str, status: PlaybackStatus, position: u64, read_at: Instant) -> PlayerState {
+        PlayerState {
+            identity: String::from("Spotify"),
+            bus_name: String::from("org.mpris.MediaPlayer2.spotify"),
+            track: Some(String::from(track)),
+            status,
+            volume: Some(1.0),
+            position: Some(Duration::from_secs(position)),
+            rate: 1.0,
+            read_at,
+        }
+    }
+
+    #[test]
+    fn it_keeps_the_state_of_unreadable_players() {
+        use mpris::PlaybackStatus::*;
+
+        let start = Instant::now();
+        let playing = state("a", Playing, 100, start);
+
+        let unreadable = state_or_previous(None, Some(&playing)).unwrap();
+        assert_eq!(unreadable.events_since(&playing), vec![]);
+
+        let readable = state("b", Playing, 0, start + Duration::from_secs(2));
+        assert_eq!(readable.events_since(&unreadable), vec![HookEvent::TrackChange]);
+
+        assert!(state_or_previous(None, None).is_none());
+    }
+
+    #[test]
+    fn it_detects_events() {
+        use mpris::PlaybackStatus::*;
+
+        let start = Instant::now();
+        let later = start + Duration::from_secs(10);
+        let playing = state("a", Playing, 100, start);
+
+        assert_eq!(state("a", Playing, 110, later).events_since(&playing), vec![]);
+        assert_eq!(
+            state("b", Playing, 0, later).events_since(&playing),
+            vec![HookEvent::TrackChange]
+        );
+        assert_eq!(
+            state("a", Paused, 110, later).events_since(&playing),
+            vec![HookEvent::Pause]
+        );
+        assert_eq!(
+            state("a", Playing, 30, later).events_since(&playing),
+            vec![HookEvent::Seek]
+        );
+
+        let paused = state("a", Paused, 100, start);
+        assert_eq!(
+            state("a", Paused, 110, later).events_since(&paused),
+            vec![HookEvent::Seek]
+        );
+        assert_eq!(
+            state("a", Stopped, 100, later).events_since(&paused),
+            vec![HookEvent::Stop]
+        );
+
+        let mut louder = state("a", Playing, 110, later);
+        louder.volume = Some(0.5);
+        assert_eq!(louder.events_since(&playing), vec![HookEvent::Volume]);
+
+        let mut without_position = state("a", Playing, 0, later);
+        without_position.position = None;
+        without_position.volume = None;
+        assert_eq!(without_position.events_since(&playing), vec![]);
+    }
+
+    #[test]
+    fn it_exports_payloads_as_environment() {
+        let payload = json!({
+            "event": "track-change",
+            "artistsString": "Martin O'Donnell, Michael Salvatori",
+            "artists": ["Martin O'Donnell", "Michael Salvatori"],
+            "isPlaying": true,
+            "trackNumber": 12,
+            "albumName": null,
+            "raw": {},
+        });
+
+        let mut environment = environment_of(&payload);
+        environment.sort();
+
+        assert_eq!(
+            environment,
+            vec![
+                (
+                    String::from("MPRIS_ARTISTS"),
+                    String::from("Martin O'Donnell, Michael Salvatori")
+                ),
+                (
+                    String::from("MPRIS_ARTISTS_STRING"),
+                    String::from("Martin O'Donnell, Michael Salvatori")
+                ),
+                (String::from("MPRIS_EVENT"), String::from("track-change")),
+                (String::from("MPRIS_IS_PLAYING"), String::from("true")),
+                (String::from("MPRIS_TRACK_NUMBER"), String::from("12")),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_parses_events() {
+        let options = Options::from_iter(vec!["on", "play,track-change", "--", "notify-send", "x"]);
+
+        assert_eq!(options.events, vec![HookEvent::Play, HookEvent::TrackChange]);
+        assert_eq!(options.command, vec!["notify-send", "x"]);
+        assert_eq!(options.concurrency, Concurrency::Queue);
+    }
+}
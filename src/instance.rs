@@ -0,0 +1,171 @@
+extern crate dbus;
+
+use self::dbus::{Connection, Message};
+use failure::Error;
+use mpris::{DBusError, Player};
+use std::fmt;
+
+/// The start of the bus name of every MPRIS player.
+pub(crate) const MPRIS_PREFIX: &str = "org.mpris.MediaPlayer2.";
+
+/// A player name given with `--player`, optionally selecting one of several running instances
+/// like `chromium#2` or `chromium@1234`.
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) struct PlayerSelector {
+    pub(crate) name: String,
+    pub(crate) instance: Option<Instance>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum Instance {
+    /// The nth instance, counting from 1, in the order that `list --verbose` shows them.
+    Nth(usize),
+    /// The instance running in the process with this ID.
+    Pid(u32),
+}
+
+impl std::str::FromStr for PlayerSelector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let whole_name = || PlayerSelector {
+            name: s.to_string(),
+            instance: None,
+        };
+
+        let separator = match s.rfind(['#', '@']) {
+            Some(separator) if separator > 0 => separator,
+            _ => return Ok(whole_name()),
+        };
+        // Names like "Radio #1" have a number in them rather than select an instance.
+        let (name, suffix) = (&s[..separator], &s[separator + 1..]);
+        if suffix.is_empty()
+            || !suffix.chars().all(|c| c.is_ascii_digit())
+            || name.ends_with(char::is_whitespace)
+        {
+            return Ok(whole_name());
+        }
+
+        let invalid = || format!("\"{}\" is not a valid instance of \"{}\"", suffix, name);
+        let instance = if s[separator..].starts_with('#') {
+            match suffix.parse().map_err(|_| invalid())? {
+                0 => return Err(String::from("Instances are counted from #1")),
+                n => Instance::Nth(n),
+            }
+        } else {
+            Instance::Pid(suffix.parse().map_err(|_| invalid())?)
+        };
+
+        Ok(PlayerSelector {
+            name: name.to_string(),
+            instance: Some(instance),
+        })
+    }
+}
+
+impl fmt::Display for PlayerSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        match self.instance {
+            Some(Instance::Nth(n)) => write!(f, "#{}", n),
+            Some(Instance::Pid(pid)) => write!(f, "@{}", pid),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Indices of the running instances of the player with this identity, in a stable order so that
+/// they are numbered the same way every time.
+pub(crate) fn instance_indices(players: &[Player], identity: &str) -> Vec<usize> {
+    let mut indices: Vec<usize> = (0..players.len())
+        .filter(|&index| players[index].identity() == identity)
+        .collect();
+    indices.sort_by_key(|&index| instance_order(players[index].bus_name()));
+    indices
+}
+
+/// Bus names are ordered by the numbers in them, so `instance999` comes before `instance1000`.
+fn instance_order(bus_name: &str) -> (usize, &str) {
+    (bus_name.len(), bus_name)
+}
+
+/// The part of the bus name that tells instances apart, like `instance1234` in
+/// `org.mpris.MediaPlayer2.chromium.instance1234`.
+pub(crate) fn instance_suffix(bus_name: &str) -> Option<&str> {
+    let player = bus_name.strip_prefix(MPRIS_PREFIX)?;
+    player.find('.').map(|dot| &player[dot + 1..])
+}
+
+/// Looks up the process IDs of players.
+pub(crate) struct ProcessIds<'c> {
+    connection: &'c Connection,
+}
+
+impl<'c> ProcessIds<'c> {
+    pub(crate) fn new(connection: &'c Connection) -> ProcessIds<'c> {
+        ProcessIds { connection }
+    }
+
+    pub(crate) fn of(&self, player: &Player) -> Result<u32, Error> {
+        let message = Message::new_method_call(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "GetConnectionUnixProcessID",
+        )
+        .map_err(failure::err_msg)?
+        .append1(player.unique_name());
+
+        let reply = self
+            .connection
+            .send_with_reply_and_block(message, 2000)
+            .map_err(DBusError::from)?;
+        Ok(reply.read1().map_err(DBusError::from)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selector(name: &str, instance: Option<Instance>) -> PlayerSelector {
+        PlayerSelector {
+            name: name.to_string(),
+            instance,
+        }
+    }
+
+    #[test]
+    fn it_parses_selectors() {
+        assert_eq!("vlc".parse(), Ok(selector("vlc", None)));
+        assert_eq!("chromium#2".parse(), Ok(selector("chromium", Some(Instance::Nth(2)))));
+        assert_eq!("mpv@1234".parse(), Ok(selector("mpv", Some(Instance::Pid(1234)))));
+        assert_eq!("#1 Radio".parse(), Ok(selector("#1 Radio", None)));
+        assert_eq!("C#".parse(), Ok(selector("C#", None)));
+        assert_eq!("Radio #1".parse(), Ok(selector("Radio #1", None)));
+        assert!("chromium#0".parse::<PlayerSelector>().is_err());
+    }
+
+    #[test]
+    fn it_displays_selectors() {
+        assert_eq!(selector("mpv", Some(Instance::Pid(1234))).to_string(), "mpv@1234");
+        assert_eq!(selector("chromium", Some(Instance::Nth(2))).to_string(), "chromium#2");
+    }
+
+    #[test]
+    fn it_finds_instance_suffixes() {
+        assert_eq!(
+            instance_suffix("org.mpris.MediaPlayer2.chromium.instance1234"),
+            Some("instance1234")
+        );
+        assert_eq!(instance_suffix("org.mpris.MediaPlayer2.spotify"), None);
+    }
+
+    #[test]
+    fn it_orders_instances_by_number() {
+        let mut names = vec!["x.instance1000", "x.instance999", "x.instance1001"];
+        names.sort_by_key(|name| instance_order(name));
+
+        assert_eq!(names, vec!["x.instance999", "x.instance1000", "x.instance1001"]);
+    }
+}
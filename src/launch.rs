@@ -0,0 +1,396 @@
+extern crate dbus;
+
+use super::{await_player, Error, PlayerNotFound, Settings};
+use self::dbus::{Connection, Message};
+use crate::bus::Finder;
+use crate::duration::Timeout;
+use crate::errors::InvalidArgument;
+use crate::instance::MPRIS_PREFIX;
+use failure::format_err;
+use mpris::{DBusError, Player};
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// Field codes that may appear in an `Exec` key. All of them are removed, as no files are opened.
+const FIELD_CODES: &str = "fFuUdDnNickvm";
+
+/// How long to wait for a launched player to start, unless `--wait` says otherwise.
+const LAUNCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Find the selected player, launching it first if it is not running.
+pub(crate) fn find_or_launch_player<'p>(
+    settings: &Settings,
+    finder: &Finder,
+) -> Result<Player<'p>, Error> {
+    let selector = settings.player.as_ref().ok_or_else(|| {
+        InvalidArgument(String::from("--launch needs the name of a player, given with --player"))
+    })?;
+
+    match settings.find_player_using(finder) {
+        Err(ref error) if PlayerNotFound::may_appear(error) => {}
+        result => return result,
+    }
+
+    launch(&selector.name, settings, finder)?;
+
+    let timeout = match settings.wait_for_player {
+        Some(timeout) => timeout.map(|Timeout(timeout)| timeout),
+        None => Some(LAUNCH_TIMEOUT),
+    };
+    await_player::find_player(settings, finder, timeout)
+}
+
+/// Start the player using D-Bus service activation, or else using its desktop file.
+fn launch(name: &str, settings: &Settings, finder: &Finder) -> Result<(), Error> {
+    let verbose = settings.verbose;
+    let connection = finder.connection()?;
+
+    let activatable = activatable_names(connection)?;
+    if let Some(bus_name) = activatable_name(&activatable, name) {
+        if verbose {
+            eprintln!("Starting {} using D-Bus activation", bus_name);
+        }
+        return start_service(connection, bus_name);
+    }
+
+    match find_desktop_entry(name) {
+        Some((path, exec)) => {
+            if verbose {
+                eprintln!("Starting {} from {}", name, path.display());
+            }
+            let arguments = exec_arguments(&exec)
+                .map_err(|error| format_err!("Cannot launch from {}: {}", path.display(), error))?;
+            Command::new(&arguments[0])
+                .args(&arguments[1..])
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .map_err(|error| format_err!("Cannot run \"{}\": {}", exec, error))?;
+            Ok(())
+        }
+        None => Err(format_err!(
+            "Cannot launch \"{}\" as there is neither a D-Bus service nor a desktop file for it",
+            name
+        )),
+    }
+}
+
+fn bus_method_call(method: &str) -> Result<Message, Error> {
+    Message::new_method_call(
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        "org.freedesktop.DBus",
+        method,
+    )
+    .map_err(failure::err_msg)
+}
+
+fn activatable_names(connection: &Connection) -> Result<Vec<String>, Error> {
+    let reply = connection
+        .send_with_reply_and_block(bus_method_call("ListActivatableNames")?, 2000)
+        .map_err(DBusError::from)?;
+    Ok(reply.read1().map_err(DBusError::from)?)
+}
+
+fn start_service(connection: &Connection, bus_name: &str) -> Result<(), Error> {
+    let message = bus_method_call("StartServiceByName")?.append2(bus_name, 0u32);
+    let timeout_ms = LAUNCH_TIMEOUT.as_millis() as i32;
+    connection
+        .send_with_reply_and_block(message, timeout_ms)
+        .map_err(DBusError::from)?;
+    Ok(())
+}
+
+/// The MPRIS bus name that ends with the player name.
+fn activatable_name<'a>(names: &'a [String], name: &str) -> Option<&'a str> {
+    names
+        .iter()
+        .find(|bus_name| {
+            bus_name
+                .strip_prefix(MPRIS_PREFIX)
+                .is_some_and(|player| caseless::default_caseless_match_str(player, name))
+        })
+        .map(String::as_str)
+}
+
+/// Directories to look for desktop files in, as in the XDG Base Directory Specification.
+fn application_dirs() -> Vec<PathBuf> {
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")));
+    let data_dirs = env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| String::from("/usr/local/share:/usr/share"));
+
+    data_home
+        .into_iter()
+        .chain(data_dirs.split(':').map(PathBuf::from))
+        .map(|dir| dir.join("applications"))
+        .collect()
+}
+
+/// The path and `Exec` key of the first desktop file for the player. Files in earlier
+/// directories hide those with the same name in later ones, even when they are `Hidden`.
+fn find_desktop_entry(name: &str) -> Option<(PathBuf, String)> {
+    let mut seen = HashSet::new();
+
+    application_dirs()
+        .into_iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flat_map(|entries| entries.filter_map(Result::ok))
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "desktop"))
+        .filter(|path| seen.insert(path.file_name().map(ToOwned::to_owned)))
+        .find_map(|path| {
+            let contents = fs::read_to_string(&path).ok()?;
+            let entry = DesktopEntry::parse(&contents);
+            let stem = path.file_stem()?.to_str()?.to_string();
+            if entry.is_named(&stem, name) && entry.is_installed() {
+                Some((path, entry.exec?))
+            } else {
+                None
+            }
+        })
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct DesktopEntry {
+    name: Option<String>,
+    exec: Option<String>,
+    try_exec: Option<String>,
+    hidden: bool,
+}
+
+impl DesktopEntry {
+    fn parse(contents: &str) -> DesktopEntry {
+        let mut entry = DesktopEntry::default();
+        let mut in_main_group = false;
+
+        for line in contents.lines().map(str::trim) {
+            if line.starts_with('[') {
+                in_main_group = line == "[Desktop Entry]";
+                continue;
+            } else if !in_main_group {
+                continue;
+            }
+
+            let (key, value) = match line.find('=') {
+                Some(equals) => (line[..equals].trim(), unescape(line[equals + 1..].trim())),
+                None => continue,
+            };
+            match key {
+                "Name" => entry.name = Some(value),
+                "Exec" => entry.exec = Some(value),
+                "TryExec" => entry.try_exec = Some(value),
+                "Hidden" => entry.hidden = value == "true",
+                _ => {}
+            }
+        }
+
+        entry
+    }
+
+    /// Desktop files are named like `spotify.desktop` or `org.videolan.VLC.desktop`, and have a
+    /// name like "VLC media player".
+    fn is_named(&self, file_stem: &str, name: &str) -> bool {
+        let matches = |candidate: &str| caseless::default_caseless_match_str(candidate, name);
+
+        matches(file_stem)
+            || file_stem.rsplit('.').next().is_some_and(matches)
+            || self.name.as_ref().is_some_and(|own_name| matches(own_name))
+    }
+
+    /// Hidden entries count as deleted, and those with a `TryExec` only count if the program
+    /// exists.
+    fn is_installed(&self) -> bool {
+        let exists = |program: &String| find_program(program).is_some();
+        !self.hidden && self.try_exec.as_ref().is_none_or(exists)
+    }
+}
+
+/// Resolve the escape sequences allowed in string values, like `\s` for a space.
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('s') => unescaped.push(' '),
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some('\\') => unescaped.push('\\'),
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+
+    unescaped
+}
+
+/// Split an `Exec` key into the program and its arguments, following the quoting rules of the
+/// Desktop Entry Specification, and remove field codes like `%U`.
+fn exec_arguments(exec: &str) -> Result<Vec<String>, Error> {
+    let mut arguments = Vec::new();
+    let mut chars = exec.chars().peekable();
+
+    loop {
+        while chars.next_if_eq(&' ').is_some() {}
+        let mut argument = String::new();
+
+        match chars.peek() {
+            None => break,
+            Some('"') => {
+                chars.next();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '`' | '$' | '\\')) => argument.push(c),
+                            _ => return Err(format_err!("Invalid escape in quoted argument")),
+                        },
+                        Some(c) => argument.push(c),
+                        None => return Err(format_err!("Missing closing quote")),
+                    }
+                }
+                if chars.peek().is_some_and(|&c| c != ' ') {
+                    return Err(format_err!("Quoted argument not followed by a space"));
+                }
+                arguments.push(argument);
+            }
+            Some(_) => {
+                let mut had_field_code = false;
+                while let Some(c) = chars.next_if(|&c| c != ' ') {
+                    if c != '%' {
+                        argument.push(c);
+                        continue;
+                    }
+                    match chars.next() {
+                        Some('%') => argument.push('%'),
+                        Some(code) if FIELD_CODES.contains(code) => had_field_code = true,
+                        Some(code) => return Err(format_err!("Unknown field code %{}", code)),
+                        None => return Err(format_err!("Incomplete field code")),
+                    }
+                }
+                // Arguments that are only field codes are left out, rather than passed empty.
+                if !(had_field_code && argument.is_empty()) {
+                    arguments.push(argument);
+                }
+            }
+        }
+    }
+
+    if arguments.is_empty() {
+        Err(format_err!("No program to run"))
+    } else {
+        Ok(arguments)
+    }
+}
+
+/// The path of a program, looked up in `PATH` unless it is absolute.
+fn find_program(program: &str) -> Option<PathBuf> {
+    let path = Path::new(program);
+    if path.is_absolute() {
+        return Some(path.to_path_buf()).filter(|path| path.is_file());
+    }
+
+    env::var_os("PATH").and_then(|paths| {
+        env::split_paths(&paths)
+            .map(|dir| dir.join(program))
+            .find(|candidate| candidate.is_file())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_finds_activatable_names() {
+        let names = vec![
+            String::from("org.freedesktop.Notifications"),
+            String::from("org.mpris.MediaPlayer2.spotify"),
+        ];
+
+        assert_eq!(activatable_name(&names, "Spotify"), Some("org.mpris.MediaPlayer2.spotify"));
+        assert_eq!(activatable_name(&names, "vlc"), None);
+        assert_eq!(activatable_name(&names, "Notifications"), None);
+    }
+
+    #[test]
+    fn it_parses_desktop_entries() {
+        let contents = "\
+[Desktop Entry]
+Name=VLC media player
+Exec=/usr/bin/vlc --started-from-file %U
+TryExec = /usr/bin/vlc
+
+[Desktop Action play-pause]
+Name=Play/Pause
+Exec=vlc --play-pause
+Hidden=true
+";
+        let entry = DesktopEntry::parse(contents);
+
+        assert_eq!(
+            entry,
+            DesktopEntry {
+                name: Some(String::from("VLC media player")),
+                exec: Some(String::from("/usr/bin/vlc --started-from-file %U")),
+                try_exec: Some(String::from("/usr/bin/vlc")),
+                hidden: false,
+            }
+        );
+        assert!(entry.is_named("vlc", "VLC"));
+        assert!(entry.is_named("org.videolan.VLC", "vlc"));
+        assert!(entry.is_named("org.videolan.VLC", "vlc media player"));
+        assert!(!entry.is_named("org.videolan.VLC", "spotify"));
+    }
+
+    #[test]
+    fn it_skips_hidden_and_missing_programs() {
+        let hidden = DesktopEntry::parse("[Desktop Entry]\nExec=spotify\nHidden=true");
+        let missing = DesktopEntry::parse("[Desktop Entry]\nExec=x\nTryExec=/nonexistent/player");
+        let plain = DesktopEntry::parse("[Desktop Entry]\nExec=spotify %U");
+
+        assert!(!hidden.is_installed());
+        assert!(!missing.is_installed());
+        assert!(plain.is_installed());
+    }
+
+    #[test]
+    fn it_splits_exec_keys() {
+        let arguments = |exec| exec_arguments(exec).unwrap();
+
+        assert_eq!(arguments("spotify %U"), vec!["spotify"]);
+        assert_eq!(
+            arguments("player  --volume=50%% --file=%f"),
+            vec!["player", "--volume=50%", "--file="]
+        );
+        assert_eq!(
+            arguments(r#""/opt/My Player/player" "say \"hi\"" "\$HOME" x"#),
+            vec!["/opt/My Player/player", "say \"hi\"", "$HOME", "x"]
+        );
+        assert_eq!(
+            arguments(&unescape(r"sh -c 'echo\s1' a\\b")),
+            vec!["sh", "-c", "'echo", "1'", "a\\b"]
+        );
+        assert!(exec_arguments("\"player").is_err());
+        assert!(exec_arguments("\"player\"x").is_err());
+        assert!(exec_arguments("player %z").is_err());
+        assert!(exec_arguments(" %U ").is_err());
+    }
+}
@@ -2,11 +2,12 @@ extern crate mpris;
 use mpris::Player;
 
 use super::{Error, Settings, Verbosity};
+use crate::bus::Finder;
+use crate::instance::{self, ProcessIds};
 
-pub(crate) fn run(settings: &Settings) -> Result<(), Error> {
-    let finder = mpris::PlayerFinder::new()?;
+pub(crate) fn run(settings: &Settings, finder: &Finder) -> Result<(), Error> {
     let players = match finder.find_all() {
-        Ok(players) => players,
+        Ok(players) => settings.without_ignored(players),
         Err(mpris::FindingError::NoPlayerFound) => vec![],
         Err(mpris::FindingError::DBusError(err)) => {
             return Err(Error::from(err));
@@ -24,20 +25,45 @@ pub(crate) fn run(settings: &Settings) -> Result<(), Error> {
         eprintln!("Found players:");
     }
 
-    for player in &players {
-        match settings.verbosity() {
-            Verbosity::Normal | Verbosity::Quiet => {
-                println!("{}", player.identity());
-            }
-            Verbosity::Verbose => {
-                println!("{}", verbose_player(player)?);
+    let process_ids = match settings.verbosity() {
+        Verbosity::Verbose => Some(ProcessIds::new(finder.connection()?)),
+        Verbosity::Normal | Verbosity::Quiet => None,
+    };
+
+    for (index, player) in players.iter().enumerate() {
+        match process_ids {
+            None => println!("{}", player.identity()),
+            Some(ref process_ids) => {
+                let label = instance_label(&players, index, process_ids);
+                println!("{}", verbose_player(player, &label)?);
             }
         }
     }
     Ok(())
 }
 
-fn verbose_player(player: &Player) -> Result<String, Error> {
+/// How to select this instance of the player, like `Chromium#2 (instance1234, PID 1234)`.
+fn instance_label(players: &[Player], index: usize, process_ids: &ProcessIds) -> String {
+    let player = &players[index];
+    let number = instance::instance_indices(players, player.identity())
+        .iter()
+        .position(|&instance| instance == index)
+        .map_or(1, |position| position + 1);
+
+    let details: Vec<String> = instance::instance_suffix(player.bus_name())
+        .map(String::from)
+        .into_iter()
+        .chain(process_ids.of(player).ok().map(|pid| format!("PID {}", pid)))
+        .collect();
+
+    if details.is_empty() {
+        format!("{}#{}", player.identity(), number)
+    } else {
+        format!("{}#{} ({})", player.identity(), number, details.join(", "))
+    }
+}
+
+fn verbose_player(player: &Player, label: &str) -> Result<String, Error> {
     use mpris::PlaybackStatus;
     use std::borrow::Cow;
 
@@ -52,20 +78,20 @@ fn verbose_player(player: &Player) -> Result<String, Error> {
 
     match playback_status {
         PlaybackStatus::Playing => Ok(format!(
-            "{identity}\t- Playing {title} by {artist}",
-            identity = player.identity(),
+            "{label}\t- Playing {title} by {artist}",
+            label = label,
             title = title,
             artist = artist,
         )),
         PlaybackStatus::Paused => Ok(format!(
-            "{identity}\t- Paused on {title} by {artist}",
-            identity = player.identity(),
+            "{label}\t- Paused on {title} by {artist}",
+            label = label,
             title = title,
             artist = artist,
         )),
         PlaybackStatus::Stopped => Ok(format!(
-            "{identity}\t- Not currently playing anything",
-            identity = player.identity(),
+            "{label}\t- Not currently playing anything",
+            label = label,
         )),
     }
 }
@@ -12,22 +12,47 @@ extern crate serde_json;
 extern crate serde_derive;
 
 extern crate failure;
-use failure::{Error, format_err};
+use failure::{Error, Fail};
 
 extern crate structopt;
 use structopt::StructOpt;
 
+mod aliases;
+mod await_player;
 mod basic_command;
+mod batch;
+mod bus;
+mod config;
+mod duration;
+mod errors;
 mod format;
+mod hooks;
+mod instance;
+mod launch;
 mod list;
 mod metadata;
+mod status;
+mod suggestion;
+mod wait;
 
 use crate::basic_command::run as basic_command;
 use crate::format::run as format;
+use crate::hooks::run as on;
 use crate::list::run as list;
 use crate::metadata::run as metadata;
-
-use mpris::{Player, PlayerFinder};
+use crate::status::run as status;
+use crate::wait::run as wait;
+
+use crate::bus::{Bus, Finder};
+use crate::config::Config;
+use crate::errors::{ErrorFormat, ErrorKind};
+use crate::instance::{Instance, PlayerSelector};
+use crate::duration::Timeout;
+use crate::suggestion::closest_match;
+use mpris::Player;
+use std::ffi::OsString;
+use std::fmt;
+use std::path::PathBuf;
 
 #[derive(Debug, PartialEq)]
 enum Verbosity {
@@ -36,6 +61,7 @@ enum Verbosity {
     Quiet,
 }
 
+#[allow(clippy::derivable_impls)]
 impl Default for Verbosity {
     fn default() -> Self {
         Verbosity::Normal
@@ -45,9 +71,10 @@ impl Default for Verbosity {
 #[derive(Debug, PartialEq)]
 enum PlayerSelection {
     Automatic,
-    WithName(String),
+    WithName(PlayerSelector),
 }
 
+#[allow(clippy::derivable_impls)]
 impl Default for PlayerSelection {
     fn default() -> Self {
         PlayerSelection::Automatic
@@ -77,11 +104,26 @@ enum Command {
     /// Go back to start of media, or go to previous media
     Previous,
 
+    /// Print the playback status of the current media. Exits with 0 only when playing
+    Status,
+
     /// Print metadata about the current media
     Metadata(metadata::Options),
 
     /// Print custom format of metadata about the current media
     Format(format::Options),
+
+    /// Run a command when players emit events
+    On(hooks::Options),
+
+    /// Wait until the player is in a given state
+    Wait(wait::Options),
+
+    /// Work with the configuration file
+    Config(config::Options),
+
+    /// Run commands read from a file or stdin, one per line, and print a result for each
+    Batch(batch::Options),
 }
 
 use structopt::clap::AppSettings;
@@ -91,6 +133,7 @@ use structopt::clap::AppSettings;
         setting = "AppSettings::SubcommandRequiredElseHelp",
         setting = "AppSettings::GlobalVersion",
         global_settings = "&[AppSettings::ColoredHelp, AppSettings::VersionlessSubcommands, AppSettings::InferSubcommands]",
+        after_help = "errors::EXIT_CODES_HELP",
     )
 )]
 struct Settings {
@@ -102,10 +145,81 @@ struct Settings {
     #[structopt(short = "q", long = "quiet", conflicts_with = "verbose", raw(global = "true"))]
     pub quiet: bool,
 
-    /// Control the player with the given name. If no player is selected then the first player
-    /// found will be controlled.
+    /// Control the player with the given name, or the only player whose name starts with it. If
+    /// no player is selected then the first player found will be controlled. Select one of
+    /// several running instances like "chromium#2", or by process ID like "mpv@1234".
     #[structopt(short = "p", long = "player", value_name = "NAME", raw(global = "true"))]
-    pub player_name: Option<String>,
+    pub player: Option<PlayerSelector>,
+
+    /// Prefer these comma-separated players, in order, when no player is selected.
+    #[structopt(
+        long = "priority",
+        value_name = "PLAYERS",
+        raw(global = "true", use_delimiter = "true", number_of_values = "1")
+    )]
+    pub priority: Vec<String>,
+
+    /// Leave out these comma-separated players when no player is selected, including when
+    /// listing or following all players.
+    #[structopt(
+        long = "ignore",
+        value_name = "PLAYERS",
+        raw(global = "true", use_delimiter = "true", number_of_values = "1")
+    )]
+    pub ignore: Vec<String>,
+
+    /// Read defaults from this file instead of $XDG_CONFIG_HOME/mprisctl/config.toml.
+    #[structopt(long = "config", value_name = "FILE", parse(from_os_str), raw(global = "true"))]
+    pub config_path: Option<PathBuf>,
+
+    /// Look for players on the session bus. This is the default.
+    #[structopt(long = "session", raw(global = "true", conflicts_with_all = r#"&["system", "bus_address"]"#))]
+    pub session: bool,
+
+    /// Look for players on the system bus, like mpd-mpris on headless machines.
+    #[structopt(long = "system", raw(global = "true", conflicts_with_all = r#"&["session", "bus_address"]"#))]
+    pub system: bool,
+
+    /// Look for players on the bus with this address, like "unix:path=/run/my-bus".
+    #[structopt(
+        long = "bus-address",
+        value_name = "ADDRESS",
+        raw(global = "true", conflicts_with_all = r#"&["session", "system"]"#)
+    )]
+    pub bus_address: Option<String>,
+
+    /// Fail instead of silently going on when the player does not accept a command, or when a
+    /// template uses a field that does not exist.
+    #[structopt(long = "strict", raw(global = "true", conflicts_with = r#""no_strict""#))]
+    pub strict: bool,
+
+    /// Silently go on when the player does not accept a command, even when the config file says
+    /// to be strict.
+    #[structopt(long = "no-strict", raw(global = "true"))]
+    pub no_strict: bool,
+
+    /// Wait for the player to start instead of failing when it is not running. Waits forever
+    /// unless a timeout is given, like "--wait=30s".
+    #[structopt(
+        long = "wait",
+        value_name = "TIMEOUT",
+        raw(global = "true", require_equals = "true")
+    )]
+    pub wait_for_player: Option<Option<Timeout>>,
+
+    /// Start the player given with --player if it is not running, and wait for it before sending
+    /// the command.
+    #[structopt(long = "launch", raw(global = "true"))]
+    pub launch: bool,
+
+    /// Print errors as text, or as a JSON object with "kind", "message" and "causes".
+    #[structopt(
+        long = "error-format",
+        value_name = "FORMAT",
+        default_value = "text",
+        raw(possible_values = "&ErrorFormat::variants()", global = "true")
+    )]
+    pub error_format: ErrorFormat,
 
     #[structopt(subcommand)]
     pub command: Command,
@@ -123,60 +237,441 @@ impl Settings {
     }
 
     fn player_selection(&self) -> PlayerSelection {
-        self.player_name.as_ref().map(|name| PlayerSelection::WithName(name.to_string())).unwrap_or_default()
+        self.player.as_ref().map(|selector| PlayerSelection::WithName(selector.clone())).unwrap_or_default()
+    }
+
+    /// Use the global options given around an alias for everything that a step of the alias does
+    /// not set itself.
+    fn inherit(&mut self, outer: &Settings) {
+        let (verbose, quiet) = (self.verbose, self.quiet);
+        self.verbose = verbose || (outer.verbose && !quiet);
+        self.quiet = quiet || (outer.quiet && !verbose);
+
+        if self.player.is_none() {
+            self.player = outer.player.clone();
+        }
+        if self.priority.is_empty() {
+            self.priority = outer.priority.clone();
+        }
+        if self.ignore.is_empty() {
+            self.ignore = outer.ignore.clone();
+        }
+        if !self.session && !self.system && self.bus_address.is_none() {
+            self.session = outer.session;
+            self.system = outer.system;
+            self.bus_address = outer.bus_address.clone();
+        }
+
+        if !self.strict && !self.no_strict {
+            self.strict = outer.strict;
+            self.no_strict = outer.no_strict;
+        }
+        self.launch = self.launch || outer.launch;
+        self.wait_for_player = self.wait_for_player.or(outer.wait_for_player);
+        self.error_format = outer.error_format;
+        self.config_path = outer.config_path.clone();
+    }
+
+    fn bus(&self) -> Bus {
+        match self.bus_address {
+            Some(ref address) => Bus::Address(address.to_string()),
+            None if self.session || !self.system => Bus::Session,
+            None => Bus::System,
+        }
+    }
+
+    fn player_finder(&self) -> Result<Finder, Error> {
+        Finder::new(self.bus())
+    }
+
+    fn find_player<'p>(&self, finder: &Finder) -> Result<Player<'p>, Error> {
+        if self.launch {
+            return launch::find_or_launch_player(self, finder);
+        }
+
+        match self.wait_for_player {
+            Some(timeout) => {
+                await_player::find_player(self, finder, timeout.map(|Timeout(timeout)| timeout))
+            }
+            None => self.find_player_using(finder),
+        }
     }
 
-    fn find_player<'p>(&self) -> Result<Player<'p>, Error> {
+    fn find_player_using<'p>(&self, finder: &Finder) -> Result<Player<'p>, Error> {
         use mpris::FindingError;
-        let finder = PlayerFinder::new()?;
 
         match self.player_selection() {
+            PlayerSelection::Automatic if !self.priority.is_empty() || !self.ignore.is_empty() => {
+                match finder.find_all() {
+                    Ok(players) => find_player_by_priority(self.without_ignored(players), &self.priority),
+                    Err(FindingError::DBusError(err)) => Err(err.into()),
+                    Err(FindingError::NoPlayerFound) => Err(PlayerNotFound::NoPlayers.into()),
+                }
+            }
             PlayerSelection::Automatic => match finder.find_active() {
                 Ok(player) => Ok(player),
                 Err(FindingError::DBusError(err)) => Err(err.into()),
-                Err(FindingError::NoPlayerFound) => Err(format_err!("Could not find any player")),
+                Err(FindingError::NoPlayerFound) => Err(PlayerNotFound::NoPlayers.into()),
             },
-            PlayerSelection::WithName(ref name) => match finder.find_all() {
-                Ok(players) => find_player_with_name(players, name),
+            PlayerSelection::WithName(ref selector) => match finder.find_all() {
+                Ok(players) => find_player_with_name(players, selector, finder),
                 Err(FindingError::DBusError(err)) => Err(err.into()),
-                Err(FindingError::NoPlayerFound) => Err(format_err!("Could not find any player with name \"{}\"", name)),
+                Err(FindingError::NoPlayerFound) => find_player_with_name(vec![], selector, finder),
             },
         }
     }
 }
 
-fn find_player_with_name<'a>(players: Vec<Player<'a>>, name: &str) -> Result<Player<'a>, Error> {
-    let found_player = players
-        .into_iter()
-        .find(|player| caseless::default_caseless_match_str(player.identity(), name));
+impl Settings {
+    /// The running players that the name and instance given with --player select, or all players
+    /// that are not ignored. Commands that follow players over time use this, so not finding the
+    /// player is not an error.
+    fn selected_players<'p>(
+        &self,
+        players: Vec<Player<'p>>,
+        finder: &Finder,
+    ) -> Result<Vec<Player<'p>>, Error> {
+        let selector = match self.player {
+            Some(ref selector) => selector,
+            None => return Ok(self.without_ignored(players)),
+        };
+
+        let identities: Vec<&str> = players.iter().map(Player::identity).collect();
+        let index = match match_player_name(&identities, &selector.name) {
+            Ok(index) => index,
+            Err(error @ PlayerNotFound::Ambiguous { .. }) => return Err(error.into()),
+            Err(_) => return Ok(vec![]),
+        };
+        let indices = match selector.instance {
+            Some(instance) => {
+                let (found, _) = find_instance(&players, index, instance, finder)?;
+                found.into_iter().collect()
+            }
+            None => instance::instance_indices(&players, players[index].identity()),
+        };
+
+        Ok(players
+            .into_iter()
+            .enumerate()
+            .filter(|(index, _)| indices.contains(index))
+            .map(|(_, player)| player)
+            .collect())
+    }
+
+    /// The running players that `selected_players` selects.
+    fn find_selected_players<'p>(&self, finder: &Finder) -> Result<Vec<Player<'p>>, Error> {
+        use mpris::FindingError;
+
+        match finder.find_all() {
+            Ok(players) => self.selected_players(players, finder),
+            Err(FindingError::NoPlayerFound) => Ok(vec![]),
+            Err(FindingError::DBusError(err)) => Err(err.into()),
+        }
+    }
+
+    /// Leave out the players that --ignore names.
+    fn without_ignored<'p>(&self, players: Vec<Player<'p>>) -> Vec<Player<'p>> {
+        let identities: Vec<String> = players.iter().map(|player| player.identity().to_string()).collect();
+        let identities: Vec<&str> = identities.iter().map(String::as_str).collect();
+        let is_ignored = |player: &Player| {
+            self.ignore
+                .iter()
+                .any(|name| selects_identity(&identities, name, player.identity()))
+        };
+
+        players.into_iter().filter(|player| !is_ignored(player)).collect()
+    }
+
+    /// Tell why nothing happens yet when the selected player is not running, suggesting the right
+    /// name when it is misspelled.
+    fn warn_when_not_running(&self, finder: &Finder) {
+        if self.player.is_none() || self.quiet {
+            return;
+        }
+        if let Err(ref error) = self.find_player_using(finder) {
+            if PlayerNotFound::may_appear(error) {
+                eprintln!("{} Waiting for it to start.", error);
+            }
+        }
+    }
+}
+
+/// No player could be found, or none with the selected name.
+#[derive(Debug)]
+enum PlayerNotFound {
+    NoPlayers,
+    NotMatched {
+        name: String,
+        available: Vec<String>,
+        suggestion: Option<String>,
+    },
+    Ambiguous {
+        name: String,
+        candidates: Vec<String>,
+    },
+    NoInstance {
+        selector: PlayerSelector,
+        instances: usize,
+    },
+}
+
+impl fmt::Display for PlayerNotFound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlayerNotFound::NoPlayers => write!(f, "Could not find any player"),
+            PlayerNotFound::NotMatched {
+                name,
+                available,
+                suggestion,
+            } => {
+                write!(f, "Could not find any player with name \"{}\".", name)?;
+                if let Some(suggestion) = suggestion {
+                    write!(f, " Did you mean \"{}\"?", suggestion)?;
+                }
+                if available.is_empty() {
+                    write!(f, " No players are running.")
+                } else {
+                    write!(f, " Running players: {}", available.join(", "))
+                }
+            }
+            PlayerNotFound::Ambiguous { name, candidates } => write!(
+                f,
+                "Player name \"{}\" is ambiguous. It matches: {}",
+                name,
+                candidates.join(", ")
+            ),
+            PlayerNotFound::NoInstance {
+                selector,
+                instances,
+            } => write!(
+                f,
+                "Could not find instance \"{}\". There are {} instance(s) running, see \"list --verbose\".",
+                selector, instances
+            ),
+        }
+    }
+}
+
+impl Fail for PlayerNotFound {}
+
+impl PlayerNotFound {
+    /// Whether the error is about a player that is not running, which could start later.
+    fn may_appear(error: &Error) -> bool {
+        match error.downcast_ref::<PlayerNotFound>() {
+            Some(PlayerNotFound::NoPlayers)
+            | Some(PlayerNotFound::NotMatched { .. })
+            | Some(PlayerNotFound::NoInstance { .. }) => true,
+            Some(PlayerNotFound::Ambiguous { .. }) | None => false,
+        }
+    }
+}
+
+fn find_player_with_name<'a>(
+    players: Vec<Player<'a>>,
+    selector: &PlayerSelector,
+    finder: &Finder,
+) -> Result<Player<'a>, Error> {
+    let identities: Vec<&str> = players.iter().map(Player::identity).collect();
+    let mut index = match_player_name(&identities, &selector.name)?;
+
+    if let Some(instance) = selector.instance {
+        let (found, instances) = find_instance(&players, index, instance, finder)?;
+        index = found.ok_or_else(|| PlayerNotFound::NoInstance {
+            selector: selector.clone(),
+            instances,
+        })?;
+    }
+
+    Ok(players.into_iter().nth(index).expect("index of a player"))
+}
+
+/// The index of the instance of the player at `index`, if it is running, and how many instances
+/// of that player are running.
+fn find_instance(
+    players: &[Player],
+    index: usize,
+    instance: Instance,
+    finder: &Finder,
+) -> Result<(Option<usize>, usize), Error> {
+    let instances = instance::instance_indices(players, players[index].identity());
+    let found = match instance {
+        Instance::Nth(n) => instances.get(n - 1).cloned(),
+        Instance::Pid(pid) => {
+            let process_ids = instance::ProcessIds::new(finder.connection()?);
+            instances
+                .iter()
+                .cloned()
+                .find(|&index| process_ids.of(&players[index]).ok() == Some(pid))
+        }
+    };
+    Ok((found, instances.len()))
+}
+
+/// Pick the player with the given identity, or the only player whose identity starts with the
+/// name. Several running instances of the same player count as one.
+fn match_player_name(identities: &[&str], name: &str) -> Result<usize, PlayerNotFound> {
+    let folded = caseless::default_case_fold_str(name);
+    let is_prefix = |identity: &str| caseless::default_case_fold_str(identity).starts_with(&folded);
+
+    if let Some(index) = identities
+        .iter()
+        .position(|identity| caseless::default_caseless_match_str(identity, name))
+    {
+        return Ok(index);
+    }
+
+    let candidates = distinct(identities.iter().cloned().filter(|identity| is_prefix(identity)));
+
+    match candidates.len() {
+        0 => Err(PlayerNotFound::NotMatched {
+            name: name.to_string(),
+            suggestion: closest_match(name, identities.iter().cloned()).map(String::from),
+            available: distinct(identities.iter().cloned()),
+        }),
+        1 => Ok(identities
+            .iter()
+            .position(|identity| is_prefix(identity))
+            .expect("matching player")),
+        _ => Err(PlayerNotFound::Ambiguous {
+            name: name.to_string(),
+            candidates,
+        }),
+    }
+}
 
-    match found_player {
-        Some(player) => Ok(player),
-        None => Err(format_err!("Could not find any player with name \"{}\"", name)),
+/// Whether the name selects the player with this identity the same way as --player does, among the
+/// running players with these identities.
+fn selects_identity(identities: &[&str], name: &str, identity: &str) -> bool {
+    match_player_name(identities, name)
+        .map(|index| caseless::default_caseless_match_str(identities[index], identity))
+        .unwrap_or(false)
+}
+
+fn distinct<'a, I: Iterator<Item = &'a str>>(identities: I) -> Vec<String> {
+    let mut distinct: Vec<String> = Vec::new();
+    for identity in identities {
+        if !distinct.iter().any(|seen| seen == identity) {
+            distinct.push(identity.to_string());
+        }
     }
+    distinct
+}
+
+/// Pick the first player in the priority list that is running, preferring playing players when
+/// several have the same priority. Players that are not in the list come last.
+fn find_player_by_priority<'a>(
+    players: Vec<Player<'a>>,
+    priority: &[String],
+) -> Result<Player<'a>, Error> {
+    let identities: Vec<String> = players.iter().map(|player| player.identity().to_string()).collect();
+    let identities: Vec<&str> = identities.iter().map(String::as_str).collect();
+
+    players
+        .into_iter()
+        .min_by_key(|player| {
+            let is_playing = player.get_playback_status().ok() == Some(mpris::PlaybackStatus::Playing);
+            (priority_rank(&identities, player.identity(), priority), !is_playing)
+        })
+        .ok_or_else(|| PlayerNotFound::NoPlayers.into())
+}
+
+fn priority_rank(identities: &[&str], identity: &str, priority: &[String]) -> usize {
+    priority
+        .iter()
+        .position(|name| selects_identity(identities, name, identity))
+        .unwrap_or(priority.len())
 }
 
 fn main() {
-    let settings = Settings::from_args();
-
-    let result = match settings.command {
-        Command::List => list(&settings),
-        Command::Play => basic_command("Play", Player::checked_play, &settings),
-        Command::Pause => basic_command("Pause", Player::checked_pause, &settings),
-        Command::TogglePause => basic_command("Play/Pause", Player::checked_play_pause, &settings),
-        Command::Next => basic_command("Next", Player::checked_next, &settings),
-        Command::Previous => basic_command("Previous", Player::checked_previous, &settings),
-        Command::Metadata(ref options) => metadata(options, &settings),
-        Command::Format(ref options) => format(options, &settings),
+    let args: Vec<OsString> = std::env::args_os().collect();
+    let (mut settings, alias) = match Settings::from_iter_safe(&args) {
+        Ok(settings) => (settings, None),
+        Err(error) => match aliases::invocation(&error, &args) {
+            Some((name, settings)) => (settings, Some(name)),
+            None => exit_with_usage_error(error),
+        },
     };
 
+    let result = Config::load(settings.config_path.as_deref()).and_then(|config| match alias {
+        Some(ref name) => aliases::run(name, &config, &settings, None),
+        None => {
+            config.apply(&mut settings)?;
+            run(&config, &settings)
+        }
+    });
+
     if let Err(error) = result {
-        eprintln!("{}", error);
-        for cause in error.iter_causes() {
-            eprintln!("\nCaused by {}", cause);
+        let kind = ErrorKind::of(&error);
+        // The status command has already printed the status.
+        if error.downcast_ref::<status::NotPlaying>().is_none() {
+            errors::report(&error, kind, settings.error_format);
+        }
+        ::std::process::exit(kind.exit_code());
+    }
+}
+
+fn run(config: &Config, settings: &Settings) -> Result<(), Error> {
+    match settings.command {
+        // These do not need a connection to the bus.
+        Command::Config(ref options) => config::run(options, config, settings),
+        Command::Batch(ref options) => batch::run(options, config, settings),
+        _ => run_using(config, settings, &settings.player_finder()?),
+    }
+}
+
+/// Run the command with the finder of an earlier command, unless it selects another bus.
+fn run_sharing(config: &Config, settings: &Settings, finder: &Finder) -> Result<(), Error> {
+    if settings.bus() == *finder.bus() {
+        run_using(config, settings, finder)
+    } else {
+        run(config, settings)
+    }
+}
+
+/// Run the command with a finder that may be shared with other commands, as in a batch.
+fn run_using(config: &Config, settings: &Settings, finder: &Finder) -> Result<(), Error> {
+    match settings.command {
+        Command::List => list(settings, finder),
+        Command::Play => basic_command("Play", "CanPlay", Player::checked_play, settings, finder),
+        Command::Pause => basic_command("Pause", "CanPause", Player::checked_pause, settings, finder),
+        Command::TogglePause => {
+            basic_command("Play/Pause", "CanPause", Player::checked_play_pause, settings, finder)
+        }
+        Command::Next => basic_command("Next", "CanGoNext", Player::checked_next, settings, finder),
+        Command::Previous => {
+            basic_command("Previous", "CanGoPrevious", Player::checked_previous, settings, finder)
+        }
+        Command::Status => status(settings, finder),
+        Command::Metadata(ref options) => metadata(options, settings, finder),
+        Command::Format(ref options) => format(options, settings, finder),
+        Command::On(ref options) => on(options, settings, finder),
+        Command::Wait(ref options) => wait(options, settings, finder),
+        Command::Config(ref options) => config::run(options, config, settings),
+        Command::Batch(_) => Err(errors::InvalidArgument(String::from(
+            "A batch cannot run another batch",
+        ))
+        .into()),
+    }
+}
+
+/// Help and version output exit successfully. Other parse failures are reported like any other
+/// error, which means finding the error format without the parsed settings.
+fn exit_with_usage_error(error: structopt::clap::Error) -> ! {
+    if !error.use_stderr() {
+        error.exit();
+    }
+
+    let format = ErrorFormat::of_raw_args(std::env::args());
+    let kind = ErrorKind::InvalidArgument;
+    match format {
+        ErrorFormat::Text => eprintln!("{}", error.message),
+        ErrorFormat::Json => {
+            let message = error.message.lines().next().unwrap_or_default();
+            let message = message.trim_start_matches("error: ").to_string();
+            errors::report(&errors::InvalidArgument(message).into(), kind, format);
         }
-        ::std::process::exit(1);
     }
+    ::std::process::exit(kind.exit_code());
 }
 
 #[cfg(test)]
@@ -205,6 +700,17 @@ mod tests {
             assert_eq!(settings.verbosity(), Verbosity::Quiet);
         }
 
+        #[test]
+        fn it_sets_strict_mode() {
+            assert!(!settings_from(vec!["x", "next"]).strict);
+            assert!(settings_from(vec!["x", "--strict", "next"]).strict);
+            assert!(settings_from(vec!["x", "--no-strict", "next"]).no_strict);
+            assert!(settings_from(vec!["x", "next", "--strict"]).strict);
+            assert!(settings_from(vec!["x", "next", "--no-strict"]).no_strict);
+            assert!(settings_from(vec!["x", "format", "--strict", "{{title}}"]).strict);
+            assert!(Settings::from_iter_safe(vec!["x", "--strict", "--no-strict", "next"]).is_err());
+        }
+
         #[test]
         fn it_sets_player_selection() {
             let settings = settings_from(vec!["x", "play"]);
@@ -213,14 +719,110 @@ mod tests {
             let settings = settings_from(vec!["x", "-p", "vlc", "play"]);
             assert_eq!(
                 settings.player_selection(),
-                PlayerSelection::WithName(String::from("vlc"))
+                PlayerSelection::WithName("vlc".parse().unwrap())
             );
 
             let settings = settings_from(vec!["x", "play", "-p", "spotify"]);
             assert_eq!(
                 settings.player_selection(),
-                PlayerSelection::WithName(String::from("spotify"))
+                PlayerSelection::WithName("spotify".parse().unwrap())
+            );
+        }
+
+        #[test]
+        fn it_sets_waiting_for_player() {
+            use std::time::Duration;
+
+            assert_eq!(settings_from(vec!["x", "play"]).wait_for_player, None);
+            assert_eq!(settings_from(vec!["x", "--wait", "play"]).wait_for_player, Some(None));
+            assert_eq!(
+                settings_from(vec!["x", "play", "--wait=30s"]).wait_for_player,
+                Some(Some(Timeout(Duration::from_secs(30))))
+            );
+        }
+
+        #[test]
+        fn it_sets_bus() {
+            assert_eq!(settings_from(vec!["x", "play"]).bus(), Bus::Session);
+            assert_eq!(settings_from(vec!["x", "--session", "play"]).bus(), Bus::Session);
+            assert_eq!(settings_from(vec!["x", "play", "--system"]).bus(), Bus::System);
+            assert_eq!(
+                settings_from(vec!["x", "--bus-address", "unix:path=/tmp/bus", "play"]).bus(),
+                Bus::Address(String::from("unix:path=/tmp/bus"))
+            );
+            assert!(Settings::from_iter_safe(vec!["x", "--system", "--session", "play"]).is_err());
+            assert!(Settings::from_iter_safe(vec!["x", "--system", "--bus-address", "a", "play"]).is_err());
+        }
+
+        #[test]
+        fn it_sets_error_format() {
+            assert_eq!(settings_from(vec!["x", "play"]).error_format, ErrorFormat::Text);
+            assert_eq!(
+                settings_from(vec!["x", "play", "--error-format", "json"]).error_format,
+                ErrorFormat::Json
             );
         }
+
+        #[test]
+        fn it_rejects_invalid_arguments() {
+            let error = Settings::from_iter_safe(vec!["x", "bogus"]).unwrap_err();
+            assert!(error.use_stderr());
+        }
+    }
+
+    #[test]
+    fn it_matches_player_names() {
+        let identities = ["VLC media player", "Spotify", "VLC media player"];
+
+        assert_eq!(match_player_name(&identities, "spotify").unwrap(), 1);
+        assert_eq!(match_player_name(&identities, "SPO").unwrap(), 1);
+        assert_eq!(match_player_name(&identities, "vlc").unwrap(), 0);
+    }
+
+    #[test]
+    fn it_suggests_player_names() {
+        let error = match_player_name(&["Spotify", "VLC media player"], "spotfy").unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Could not find any player with name \"spotfy\". Did you mean \"Spotify\"? \
+             Running players: Spotify, VLC media player"
+        );
+        assert_eq!(
+            match_player_name(&[], "vlc").unwrap_err().to_string(),
+            "Could not find any player with name \"vlc\". No players are running."
+        );
+    }
+
+    #[test]
+    fn it_rejects_ambiguous_player_names() {
+        let error = match_player_name(&["Spotify", "SMPlayer"], "s").unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Player name \"s\" is ambiguous. It matches: Spotify, SMPlayer"
+        );
+    }
+
+    #[test]
+    fn it_ranks_players_by_priority() {
+        let identities = ["Spotify", "mpv", "VLC media player", "SMPlayer"];
+        let priority = vec![String::from("spotify"), String::from("mp"), String::from("s")];
+
+        assert_eq!(priority_rank(&identities, "Spotify", &priority), 0);
+        assert_eq!(priority_rank(&identities, "mpv", &priority), 1);
+        assert_eq!(priority_rank(&identities, "VLC media player", &priority), 3);
+        assert_eq!(priority_rank(&identities, "SMPlayer", &priority), 3);
+    }
+
+    #[test]
+    fn it_names_the_capability_of_unsupported_commands() {
+        let error: Error = basic_command::Unsupported::new("Next", "CanGoNext", "Spotify").into();
+
+        assert_eq!(ErrorKind::of(&error), ErrorKind::Unsupported);
+        assert_eq!(
+            error.to_string(),
+            "Next command not sent to Spotify as player does not accept it (CanGoNext is false)."
+        );
     }
 }
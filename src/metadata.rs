@@ -3,8 +3,11 @@ extern crate serde;
 extern crate serde_json;
 
 use super::{Error, Settings};
+use crate::bus::Finder;
+use crate::errors::InvalidArgument;
 use std::collections::HashMap;
 use std::fmt::Display;
+use std::time::Duration;
 use structopt::StructOpt;
 
 use mpris::{DBusError, LoopStatus, Metadata, PlaybackStatus, Player, Progress, TrackID};
@@ -14,14 +17,26 @@ pub struct Options {
     #[structopt(
         short = "f",
         long = "format",
-        default_value = "text",
         raw(possible_values = "&Format::variants()")
     )]
-    /// Render metadata in this format.
-    format: Format,
+    /// Render metadata in this format. Defaults to text.
+    format: Option<Format>,
+}
+
+impl Options {
+    pub(crate) fn set_default_format(&mut self, format: &str) -> Result<(), Error> {
+        if self.format.is_none() {
+            let format = format.parse().map_err(|error| {
+                InvalidArgument(format!("Invalid metadata-format in the config: {}", error))
+            })?;
+            self.format = Some(format);
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
+#[allow(clippy::upper_case_acronyms)]
 enum Format {
     Text,
     JSON,
@@ -45,7 +60,7 @@ impl std::str::FromStr for Format {
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct MetadataView<'a> {
     album_artists: Option<Vec<&'a str>>,
@@ -76,10 +91,110 @@ pub(crate) struct MetadataView<'a> {
     is_shuffled: bool,
     is_stopped: bool,
 
+    #[serde(flatten)]
+    player: PlayerInfo,
+
     raw: HashMap<String, serde_json::Value>,
 }
 
-fn playback_status_str(playback_status: PlaybackStatus) -> &'static str {
+/// Identity and capabilities of a player. These rarely change, so they are read once when watching.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PlayerInfo {
+    identity: String,
+    desktop_entry: Option<String>,
+    bus_name: String,
+    can_play: bool,
+    can_pause: bool,
+    can_seek: bool,
+    can_go_next: bool,
+    can_go_previous: bool,
+    can_control: bool,
+    has_track_list: Option<bool>,
+    supported_uri_schemes: Vec<String>,
+    supported_mime_types: Vec<String>,
+    is_fullscreen: Option<bool>,
+    minimum_rate: Option<f64>,
+    maximum_rate: Option<f64>,
+}
+
+impl PlayerInfo {
+    /// Properties that many players leave out are read as null or empty instead of failing.
+    pub(crate) fn from_player(player: &Player) -> Result<PlayerInfo, DBusError> {
+        Ok(PlayerInfo {
+            identity: player.identity().to_string(),
+            desktop_entry: player.get_desktop_entry().ok().flatten(),
+            bus_name: player.bus_name().to_string(),
+            can_play: player.can_play()?,
+            can_pause: player.can_pause()?,
+            can_seek: player.can_seek()?,
+            can_go_next: player.can_go_next()?,
+            can_go_previous: player.can_go_previous()?,
+            can_control: player.can_control()?,
+            has_track_list: player.get_has_track_list().ok(),
+            supported_uri_schemes: player.get_supported_uri_schemes().unwrap_or_default(),
+            supported_mime_types: player.get_supported_mime_types().unwrap_or_default(),
+            is_fullscreen: player.get_fullscreen().ok().flatten(),
+            minimum_rate: player.get_minimum_playback_rate().ok(),
+            maximum_rate: player.get_maximum_playback_rate().ok(),
+        })
+    }
+
+    /// A Spotify player that can do everything but change its rate or track list.
+    pub(crate) fn sample() -> PlayerInfo {
+        PlayerInfo {
+            identity: String::from("Spotify"),
+            desktop_entry: Some(String::from("spotify")),
+            bus_name: String::from("org.mpris.MediaPlayer2.spotify"),
+            can_play: true,
+            can_pause: true,
+            can_seek: true,
+            can_go_next: true,
+            can_go_previous: true,
+            can_control: true,
+            has_track_list: Some(false),
+            supported_uri_schemes: vec![String::from("spotify")],
+            supported_mime_types: vec![],
+            is_fullscreen: None,
+            minimum_rate: Some(1.0),
+            maximum_rate: Some(1.0),
+        }
+    }
+}
+
+/// A `MetadataView` together with whether it describes the selected player.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PlayerView<'a> {
+    #[serde(flatten)]
+    metadata: MetadataView<'a>,
+    is_active: bool,
+}
+
+impl<'a> PlayerView<'a> {
+    pub(crate) fn new(metadata: MetadataView<'a>, is_active: bool) -> Self {
+        PlayerView {
+            metadata,
+            is_active,
+        }
+    }
+
+    pub(crate) fn is_active(&self) -> bool {
+        self.is_active
+    }
+}
+
+impl PlayerView<'static> {
+    /// The sample metadata as the selected player, like the only entry of `--all-players`.
+    pub(crate) fn sample() -> PlayerView<'static> {
+        PlayerView {
+            metadata: MetadataView::sample(),
+            is_active: true,
+        }
+    }
+}
+
+pub(crate) fn playback_status_str(playback_status: PlaybackStatus) -> &'static str {
     use self::PlaybackStatus::*;
 
     match playback_status {
@@ -97,10 +212,22 @@ fn loop_status_str(loop_status: LoopStatus) -> &'static str {
     }
 }
 
+/// What tells tracks apart: the track ID, or the title for players that do not have track IDs.
+pub(crate) fn track_of(metadata: &Metadata) -> Option<String> {
+    metadata
+        .track_id()
+        .map(String::from)
+        .or_else(|| metadata.title().map(String::from))
+}
+
 fn join_option_string(list: Option<Vec<&str>>) -> Option<String> {
     list.map(|a| a.join(", "))
 }
 
+fn in_microseconds(duration: Duration) -> u64 {
+    duration.as_secs() * 1_000_000 + u64::from(duration.subsec_micros())
+}
+
 impl<'a> MetadataView<'a> {
     pub(crate) fn from_player(
         metadata: &'a Metadata,
@@ -113,12 +240,12 @@ impl<'a> MetadataView<'a> {
         let loop_status_str = loop_status_str(loop_status);
 
         let position = player.get_position()?;
-        let position_in_microseconds =
-            position.as_secs() + u64::from(position.subsec_nanos()) * 1_000_000_000;
+        let position_in_microseconds = in_microseconds(position);
         let position_in_seconds = position.as_secs();
 
         let playback_rate = player.get_playback_rate()?;
         let shuffled = player.get_shuffle()?;
+        let player_info = PlayerInfo::from_player(player)?;
 
         Ok(MetadataView {
             album_artists: metadata.album_artists(),
@@ -149,11 +276,16 @@ impl<'a> MetadataView<'a> {
             is_paused: playback_status == PlaybackStatus::Paused,
             is_stopped: playback_status == PlaybackStatus::Stopped,
 
+            player: player_info,
+
             raw: HashMap::new(), // TODO: mpris does not allow us to read this without consuming the entire Metadata.
         })
     }
 
-    pub(crate) fn from_progress(progress: &'a Progress) -> Result<MetadataView<'a>, DBusError> {
+    pub(crate) fn from_progress(
+        progress: &'a Progress,
+        player_info: &PlayerInfo,
+    ) -> Result<MetadataView<'a>, DBusError> {
         let playback_status = progress.playback_status();
         let playback_status_str = playback_status_str(playback_status);
 
@@ -161,8 +293,7 @@ impl<'a> MetadataView<'a> {
         let loop_status_str = loop_status_str(loop_status);
 
         let position = progress.position();
-        let position_in_microseconds =
-            position.as_secs() + u64::from(position.subsec_nanos()) * 1_000_000_000;
+        let position_in_microseconds = in_microseconds(position);
         let position_in_seconds = position.as_secs();
 
         let metadata = progress.metadata();
@@ -196,17 +327,61 @@ impl<'a> MetadataView<'a> {
             is_paused: playback_status == PlaybackStatus::Paused,
             is_stopped: playback_status == PlaybackStatus::Stopped,
 
+            player: player_info.clone(),
+
             raw: HashMap::new(), // TODO: mpris does not allow us to read this without consuming the entire Metadata.
         })
     }
 }
 
-pub(crate) fn run(options: &Options, settings: &Settings) -> Result<(), Error> {
-    let player = settings.find_player()?;
+impl MetadataView<'static> {
+    /// "In Amber Clad" from the Halo 2 soundtrack, playing at 02:12 of 03:50 in Spotify. Used by
+    /// `format --sample` and the template checks.
+    pub(crate) fn sample() -> MetadataView<'static> {
+        let artists = vec!["Martin O'Donnell", "Michael Salvatori"];
+
+        MetadataView {
+            album_artists: Some(artists.clone()),
+            album_artists_string: join_option_string(Some(artists.clone())),
+            album_name: Some("Halo 2, Vol. 1 (Original Game Soundtrack)"),
+            art_url: Some("https://i.scdn.co/image/906dcddc9780f7870566c6a5892633a374ab78f5"),
+            artists: Some(artists.clone()),
+            artists_string: join_option_string(Some(artists)),
+            auto_rating: Some(0.32),
+            disc_number: Some(1),
+            length_in_microseconds: Some(230_000_000),
+            length_in_seconds: Some(230),
+            loop_status: loop_status_str(LoopStatus::None),
+            playback_rate: 1.0,
+            playback_status: playback_status_str(PlaybackStatus::Playing),
+            position_in_microseconds: 132_623_000,
+            position_in_seconds: 132,
+            title: Some("In Amber Clad"),
+            track_id: Some(String::from("spotify:track:6avJNmUB4ZlphOS9jkH5HV")),
+            track_number: Some(12),
+            url: Some("https://open.spotify.com/track/6avJNmUB4ZlphOS9jkH5HV"),
+            volume: 0.6300000190734863,
+
+            is_looping_playlist: false,
+            is_looping_track: false,
+            is_paused: false,
+            is_playing: true,
+            is_shuffled: false,
+            is_stopped: false,
+
+            player: PlayerInfo::sample(),
+
+            raw: HashMap::new(),
+        }
+    }
+}
+
+pub(crate) fn run(options: &Options, settings: &Settings, finder: &Finder) -> Result<(), Error> {
+    let player = settings.find_player(finder)?;
     let metadata = player.get_metadata()?;
     let metadata_view = MetadataView::from_player(&metadata, &player)?;
 
-    match options.format {
+    match options.format.unwrap_or(Format::Text) {
         Format::Text => print_metadata(&metadata_view),
         Format::JSON => match serde_json::to_string(&metadata_view) {
             Ok(json) => {
@@ -238,6 +413,23 @@ fn print_metadata<'a>(view: &'a MetadataView<'a>) -> Result<(), Error> {
     print_text_field("Looping", &Some(view.loop_status));
     print_text_field("Shuffled", &Some(view.is_shuffled));
     print_text_field("Volume (unitless)", &Some(view.volume));
+
+    let player = &view.player;
+    print_text_field("Identity", &Some(&player.identity));
+    print_text_field("Desktop entry", &player.desktop_entry);
+    print_text_field("Bus name", &Some(&player.bus_name));
+    print_text_field("Can play", &Some(player.can_play));
+    print_text_field("Can pause", &Some(player.can_pause));
+    print_text_field("Can seek", &Some(player.can_seek));
+    print_text_field("Can go next", &Some(player.can_go_next));
+    print_text_field("Can go previous", &Some(player.can_go_previous));
+    print_text_field("Can control", &Some(player.can_control));
+    print_text_field("Has track list", &player.has_track_list);
+    print_text_field("URI schemes", &Some(player.supported_uri_schemes.join(", ")));
+    print_text_field("MIME types", &Some(player.supported_mime_types.join(", ")));
+    print_text_field("Fullscreen", &player.is_fullscreen);
+    print_text_field("Minimum rate", &player.minimum_rate);
+    print_text_field("Maximum rate", &player.maximum_rate);
     Ok(())
 }
 
@@ -255,3 +447,15 @@ fn print_text_field<T: Display>(title: &'static str, value: &Option<T>) {
         None => println!("{}:", title),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_counts_positions_in_microseconds() {
+        assert_eq!(in_microseconds(Duration::from_millis(1_500)), 1_500_000);
+        assert_eq!(in_microseconds(Duration::new(2, 999)), 2_000_000);
+        assert_eq!(in_microseconds(Duration::from_micros(42)), 42);
+    }
+}
@@ -0,0 +1,37 @@
+extern crate mpris;
+
+use super::{Error, Settings};
+use crate::bus::Finder;
+use crate::metadata::playback_status_str;
+use failure::Fail;
+use mpris::PlaybackStatus;
+use std::fmt;
+
+/// The player is not playing. Not a failure as such, but scripts can see it in the exit code.
+#[derive(Debug)]
+pub(crate) struct NotPlaying(pub(crate) PlaybackStatus);
+
+impl fmt::Display for NotPlaying {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", playback_status_str(self.0))
+    }
+}
+
+impl Fail for NotPlaying {}
+
+pub(crate) fn run(settings: &Settings, finder: &Finder) -> Result<(), Error> {
+    let player = settings.find_player(finder)?;
+    let status = player.get_playback_status()?;
+
+    if settings.verbose {
+        eprintln!("Status of {}:", player.identity());
+    }
+    if !settings.quiet {
+        println!("{}", playback_status_str(status));
+    }
+
+    match status {
+        PlaybackStatus::Playing => Ok(()),
+        status => Err(NotPlaying(status).into()),
+    }
+}
@@ -0,0 +1,46 @@
+extern crate strsim;
+
+/// Find the candidate that is closest to the given (misspelled) name, if any of them are close
+/// enough to be a plausible suggestion.
+pub(crate) fn closest_match<'c, I>(name: &str, candidates: I) -> Option<&'c str>
+where
+    I: IntoIterator<Item = &'c str>,
+{
+    let name = caseless::default_case_fold_str(name);
+    let max_distance = (name.chars().count() / 3).max(2);
+
+    candidates
+        .into_iter()
+        .map(|candidate| {
+            let distance = strsim::damerau_levenshtein(
+                &name,
+                &caseless::default_case_fold_str(candidate),
+            );
+            (distance, candidate)
+        })
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_finds_closest_match() {
+        let candidates = vec!["artists", "artistsString", "albumArtists"];
+
+        assert_eq!(
+            closest_match("artistString", candidates.clone()),
+            Some("artistsString")
+        );
+        assert_eq!(closest_match("ARTIST", candidates.clone()), Some("artists"));
+        assert_eq!(closest_match("volume", candidates), None);
+    }
+
+    #[test]
+    fn it_handles_no_candidates() {
+        assert_eq!(closest_match("anything", vec![]), None);
+    }
+}
@@ -0,0 +1,249 @@
+extern crate mpris;
+
+use super::{Error, Settings};
+use crate::bus::Finder;
+use crate::duration::parse_duration;
+use failure::Fail;
+use crate::metadata::track_of;
+use mpris::PlaybackStatus;
+use std::collections::HashSet;
+use std::fmt;
+use std::thread;
+use std::time::{Duration, Instant};
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug)]
+pub struct Options {
+    #[structopt(
+        long = "until",
+        value_name = "CONDITION",
+        raw(possible_values = "&Condition::variants()")
+    )]
+    /// The condition to wait for.
+    until: Condition,
+
+    #[structopt(
+        long = "timeout",
+        value_name = "DURATION",
+        parse(try_from_str = "parse_duration")
+    )]
+    /// Give up after this long, like "30s" or "5m", and exit with code 3. Waits forever if not
+    /// given.
+    timeout: Option<Duration>,
+
+    #[structopt(long = "interval", value_name = "MILLISECONDS", default_value = "250")]
+    /// Check the player at this interval.
+    interval: u32,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Condition {
+    Playing,
+    Paused,
+    Stopped,
+    TrackChange,
+    PlayerAppeared,
+}
+
+impl Condition {
+    fn variants() -> [&'static str; 5] {
+        ["playing", "paused", "stopped", "track-change", "player-appeared"]
+    }
+
+    fn name(self) -> &'static str {
+        use self::Condition::*;
+
+        match self {
+            Playing => "playing",
+            Paused => "paused",
+            Stopped => "stopped",
+            TrackChange => "track-change",
+            PlayerAppeared => "player-appeared",
+        }
+    }
+
+    fn is_met(self, initial: &Snapshot, current: &Snapshot) -> bool {
+        use self::Condition::*;
+
+        let status = current.selected.as_ref().map(|player| player.status);
+        match self {
+            Playing => status == Some(PlaybackStatus::Playing),
+            Paused => status == Some(PlaybackStatus::Paused),
+            Stopped => status == Some(PlaybackStatus::Stopped),
+            TrackChange => {
+                let track = |snapshot: &Snapshot| {
+                    snapshot
+                        .selected
+                        .as_ref()
+                        .and_then(|player| player.track.clone())
+                };
+                track(current).is_some() && track(current) != track(initial)
+            }
+            PlayerAppeared => current
+                .unique_names
+                .iter()
+                .any(|name| !initial.unique_names.contains(name)),
+        }
+    }
+}
+
+impl std::str::FromStr for Condition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use self::Condition::*;
+
+        match caseless::default_case_fold_str(s).as_str() {
+            "playing" => Ok(Playing),
+            "paused" => Ok(Paused),
+            "stopped" => Ok(Stopped),
+            "track-change" => Ok(TrackChange),
+            "player-appeared" => Ok(PlayerAppeared),
+            _ => Err(format!("\"{}\" is not a valid condition", s)),
+        }
+    }
+}
+
+/// The condition was not met before the timeout.
+#[derive(Debug)]
+pub(crate) struct TimedOut {
+    condition: Condition,
+}
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Timed out waiting for {}", self.condition.name())
+    }
+}
+
+impl Fail for TimedOut {}
+
+/// What the condition is checked against.
+#[derive(Debug, Default)]
+struct Snapshot {
+    selected: Option<SelectedPlayer>,
+    unique_names: HashSet<String>,
+}
+
+#[derive(Debug)]
+struct SelectedPlayer {
+    status: PlaybackStatus,
+    track: Option<String>,
+}
+
+impl Snapshot {
+    /// Players that cannot be found or read are treated as not running, as they could show up
+    /// later.
+    fn read(finder: &Finder, settings: &Settings) -> Result<Snapshot, Error> {
+        let unique_names = settings
+            .find_selected_players(finder)?
+            .iter()
+            .map(|player| player.unique_name().to_string())
+            .collect();
+
+        let selected = settings.find_player_using(finder).ok().and_then(|player| {
+            Some(SelectedPlayer {
+                status: player.get_playback_status().ok()?,
+                track: track_of(&player.get_metadata().ok()?),
+            })
+        });
+
+        Ok(Snapshot {
+            selected,
+            unique_names,
+        })
+    }
+}
+
+pub(crate) fn run(options: &Options, settings: &Settings, finder: &Finder) -> Result<(), Error> {
+    let started = Instant::now();
+    settings.warn_when_not_running(finder);
+    let initial = Snapshot::read(finder, settings)?;
+    let mut current = None;
+
+    loop {
+        if options.until.is_met(&initial, current.as_ref().unwrap_or(&initial)) {
+            if settings.verbose {
+                eprintln!("Condition {} met", options.until.name());
+            }
+            return Ok(());
+        }
+
+        if let Some(timeout) = options.timeout {
+            if started.elapsed() >= timeout {
+                return Err(TimedOut {
+                    condition: options.until,
+                }
+                .into());
+            }
+        }
+
+        thread::sleep(Duration::from_millis(u64::from(options.interval)));
+        current = Some(Snapshot::read(finder, settings)?);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(status: Option<PlaybackStatus>, track: &str, names: &[&str]) -> Snapshot {
+        Snapshot {
+            selected: status.map(|status| SelectedPlayer {
+                status,
+                track: Some(String::from(track)),
+            }),
+            unique_names: names.iter().map(|name| name.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn it_checks_playback_status() {
+        use mpris::PlaybackStatus::*;
+
+        let initial = Snapshot::default();
+
+        assert!(Condition::Playing.is_met(&initial, &snapshot(Some(Playing), "a", &[":1"])));
+        assert!(!Condition::Playing.is_met(&initial, &snapshot(Some(Paused), "a", &[":1"])));
+        assert!(Condition::Paused.is_met(&initial, &snapshot(Some(Paused), "a", &[":1"])));
+        assert!(Condition::Stopped.is_met(&initial, &snapshot(Some(Stopped), "a", &[":1"])));
+        assert!(!Condition::Stopped.is_met(&initial, &Snapshot::default()));
+    }
+
+    #[test]
+    fn it_checks_track_changes() {
+        use mpris::PlaybackStatus::*;
+
+        let initial = snapshot(Some(Playing), "a", &[":1"]);
+
+        assert!(!Condition::TrackChange.is_met(&initial, &snapshot(Some(Paused), "a", &[":1"])));
+        assert!(Condition::TrackChange.is_met(&initial, &snapshot(Some(Playing), "b", &[":1"])));
+        assert!(!Condition::TrackChange.is_met(&initial, &Snapshot::default()));
+        assert!(Condition::TrackChange.is_met(
+            &Snapshot::default(),
+            &snapshot(Some(Playing), "a", &[":1"])
+        ));
+    }
+
+    #[test]
+    fn it_checks_appeared_players() {
+        use mpris::PlaybackStatus::*;
+
+        let initial = snapshot(Some(Playing), "a", &[":1"]);
+
+        assert!(!Condition::PlayerAppeared.is_met(&initial, &initial));
+        assert!(!Condition::PlayerAppeared.is_met(&initial, &Snapshot::default()));
+        assert!(Condition::PlayerAppeared.is_met(
+            &initial,
+            &snapshot(Some(Playing), "a", &[":1", ":2"])
+        ));
+    }
+
+    #[test]
+    fn it_parses_options() {
+        let options = Options::from_iter(vec!["wait", "--until", "track-change", "--timeout", "30s"]);
+
+        assert_eq!(options.until, Condition::TrackChange);
+        assert_eq!(options.timeout, Some(Duration::from_secs(30)));
+    }
+}